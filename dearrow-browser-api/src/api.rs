@@ -40,6 +40,72 @@ pub struct StatusResponse {
     pub server_git_dirty: Option<bool>,
     pub server_build_timestamp: Option<i64>,
     pub server_startup_timestamp: i64,
+    /// The most recent reload job, including the initial load
+    pub reload: Option<ReloadJobStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReloadPhase {
+    Thumbnails,
+    Titles,
+    Usernames,
+    VipUsers,
+    SponsorTimes,
+    Warnings,
+    /// Sorting the new database and calculating statistics
+    Indexing,
+    /// Replacing the live database
+    Swap,
+}
+
+impl ReloadPhase {
+    pub const ALL: [ReloadPhase; 8] = [
+        ReloadPhase::Thumbnails,
+        ReloadPhase::Titles,
+        ReloadPhase::Usernames,
+        ReloadPhase::VipUsers,
+        ReloadPhase::SponsorTimes,
+        ReloadPhase::Warnings,
+        ReloadPhase::Indexing,
+        ReloadPhase::Swap,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ReloadPhaseProgress {
+    pub phase: ReloadPhase,
+    /// Number of CSV rows read so far, always 0 for phases that don't read files
+    pub rows: u64,
+    pub elapsed_ms: u64,
+    pub finished: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadOutcome {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReloadJobStatus {
+    pub id: u64,
+    pub requested_by: RcStr,
+    pub started: i64,
+    pub elapsed_ms: u64,
+    /// Phases that were started so far, in order
+    pub phases: Vec<ReloadPhaseProgress>,
+    pub outcome: ReloadOutcome,
+    /// Set if the outcome is `Failed`
+    pub error: Option<RcStr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReadinessResponse {
+    /// Whether the database is loaded and the data routes can be used
+    pub ready: bool,
+    /// The most recent reload job, including the initial load
+    pub reload: Option<ReloadJobStatus>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -93,6 +159,15 @@ pub struct User {
     pub active_warning_count: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct UserSearchResult {
+    pub users: Vec<User>,
+    /// Usernames of the returned users, which are also used by at least one other user
+    pub username_collisions: Vec<RcStr>,
+    /// Set if more users matched the query than were returned
+    pub truncated: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Video {
     pub video_id: RcStr,
@@ -148,3 +223,140 @@ pub struct ApiWarning {
     pub message: RcStr,
     pub active: bool,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub user_id: RcStr,
+    pub username: Option<RcStr>,
+    pub vip: bool,
+    pub title_count: u64,
+    pub thumbnail_count: u64,
+    pub locked_count: u64,
+    pub average_score: f64,
+    pub recent_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Leaderboards {
+    pub titles: Vec<LeaderboardEntry>,
+    pub thumbnails: Vec<LeaderboardEntry>,
+    pub locked: Vec<LeaderboardEntry>,
+    pub average_score: Vec<LeaderboardEntry>,
+    pub recently_active: Vec<LeaderboardEntry>,
+}
+
+/// The reason why a submission would not be displayed by the `DeArrow` extension
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BrandingHiddenReason {
+    BelowVoteThreshold,
+    Removed,
+    ShadowHidden,
+    MissingVotes,
+    MissingTimestamp,
+    UnverifiedNotLocked,
+    /// The submission could be displayed, but a higher ranked submission was picked instead
+    Outranked,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BrandingTitle {
+    pub title: ApiTitle,
+    /// `None` if this is the title that would be displayed
+    pub hidden_reason: Option<BrandingHiddenReason>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct BrandingThumbnail {
+    pub thumbnail: ApiThumbnail,
+    /// `None` if this is the thumbnail that would be displayed
+    pub hidden_reason: Option<BrandingHiddenReason>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct VideoBranding {
+    pub video_id: RcStr,
+    /// All titles submitted for this video. Titles that could be displayed come first, in the order
+    /// they are ranked by the `SponsorBlock` server, followed by hidden titles, newest first.
+    pub titles: Vec<BrandingTitle>,
+    /// All thumbnails submitted for this video, ordered like `titles`
+    pub thumbnails: Vec<BrandingThumbnail>,
+    /// Fraction of the video used for the random thumbnail, if no thumbnail would be displayed
+    pub random_time: f64,
+    pub video_duration: Option<f64>,
+}
+
+/// Payload of the `reload_started` event of the submission feed
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReloadStartedEvent {
+    pub timestamp: i64,
+}
+
+/// Payload of the `reload_finished` event of the submission feed
+///
+/// Sent once the new database is live, before the events for new submissions.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReloadFinishedEvent {
+    pub last_updated: i64,
+    pub new_titles: u64,
+    pub new_thumbnails: u64,
+}
+
+/// Payload of the `reload_failed` event of the submission feed
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReloadFailedEvent {
+    pub error: RcStr,
+}
+
+/// Payload of the `lagged` event of the submission feed
+///
+/// Sent when the subscriber could not keep up and some events were dropped.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FeedLaggedEvent {
+    pub skipped: u64,
+}
+
+/// A flag of a title or thumbnail, as used by webhooks
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionFlag {
+    Original,
+    Locked,
+    ShadowHidden,
+    /// Only used by titles
+    Unverified,
+    Removed,
+}
+
+/// A change found by a reload, reported to the webhooks whose rules it matches
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    NewTitle {
+        title: ApiTitle,
+    },
+    NewThumbnail {
+        thumbnail: ApiThumbnail,
+    },
+    /// The flags of an already known title changed
+    TitleUpdated {
+        title: ApiTitle,
+        added_flags: Vec<SubmissionFlag>,
+        removed_flags: Vec<SubmissionFlag>,
+    },
+    /// The flags of an already known thumbnail changed
+    ThumbnailUpdated {
+        thumbnail: ApiThumbnail,
+        added_flags: Vec<SubmissionFlag>,
+        removed_flags: Vec<SubmissionFlag>,
+    },
+}
+
+/// Body of a webhook request, sent once per reload with all matching changes
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookPayload {
+    /// Name of the webhook in the server config
+    pub webhook: RcStr,
+    /// The `last_updated` timestamp of the database the changes were found in
+    pub last_updated: i64,
+    pub events: Vec<WebhookEvent>,
+}
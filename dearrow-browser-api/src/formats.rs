@@ -0,0 +1,150 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{error::Error, fmt::Display};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodings the API can respond with, selected using the `Accept` header
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    pub const fn mime_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    /// Recognizes the format from a `Content-Type` or `Accept` media type, ignoring its parameters
+    pub fn from_mime_type(mime: &str) -> Option<Format> {
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        if essence.eq_ignore_ascii_case("application/json") {
+            Some(Format::Json)
+        } else if ["application/msgpack", "application/x-msgpack", "application/vnd.msgpack"].iter().any(|m| essence.eq_ignore_ascii_case(m)) {
+            Some(Format::MessagePack)
+        } else if essence.eq_ignore_ascii_case("application/cbor") {
+            Some(Format::Cbor)
+        } else {
+            None
+        }
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, FormatError> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(FormatError::new),
+            // named, so that structs decode as maps in other languages
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(FormatError::new),
+            Format::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(value, &mut buffer).map_err(FormatError::new)?;
+                Ok(buffer)
+            },
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, FormatError> {
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(FormatError::new),
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(FormatError::new),
+            Format::Cbor => ciborium::from_reader(bytes).map_err(FormatError::new),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FormatError(Box<dyn Error + Send + Sync>);
+
+impl FormatError {
+    fn new(err: impl Error + Send + Sync + 'static) -> FormatError {
+        FormatError(Box::new(err))
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+    use super::Format;
+    use crate::sync::ApiTitle;
+
+    fn title(username: Option<&str>) -> ApiTitle {
+        ApiTitle {
+            uuid: "0123456789abcdef".into(),
+            video_id: "dQw4w9WgXcQ".into(),
+            title: "Never gonna give you up 🎵".into(),
+            user_id: "a".repeat(64).into(),
+            time_submitted: 1_700_000_000_000,
+            votes: 3,
+            downvotes: -1,
+            original: false,
+            locked: true,
+            shadow_hidden: false,
+            unverified: true,
+            removed: false,
+            votes_missing: false,
+            score: 1,
+            username: username.map(Into::into),
+            vip: true,
+        }
+    }
+
+    #[test]
+    fn title_round_trips() {
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            for value in [title(Some("Alice")), title(None)] {
+                let encoded = format.encode(&value).unwrap();
+                let decoded: ApiTitle = format.decode(&encoded).unwrap();
+                assert!(decoded == value, "{format:?} round trip changed the title");
+            }
+        }
+    }
+
+    #[test]
+    fn title_list_round_trips() {
+        let value = vec![title(Some("Alice")), title(None)];
+        for format in [Format::MessagePack, Format::Cbor] {
+            let decoded: Vec<ApiTitle> = format.decode(&format.encode(&value).unwrap()).unwrap();
+            assert!(decoded == value, "{format:?} round trip changed the titles");
+        }
+    }
+
+    #[test]
+    fn mime_types_are_recognized() {
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            assert_eq!(Format::from_mime_type(format.mime_type()), Some(format));
+        }
+        assert_eq!(Format::from_mime_type("Application/X-MsgPack; charset=binary"), Some(Format::MessagePack));
+        assert_eq!(Format::from_mime_type("text/html"), None);
+    }
+}
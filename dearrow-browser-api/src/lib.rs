@@ -129,3 +129,9 @@ pub mod string {
 
     include!("api.rs");
 }
+
+/// Minimum query length accepted by the user search endpoint
+pub const USER_SEARCH_MIN_LENGTH: usize = 3;
+
+#[cfg(feature = "formats")]
+pub mod formats;
@@ -25,6 +25,7 @@ use yew_router::prelude::Link;
 
 use crate::components::modals::{async_tasks::AsyncTasksModal, settings::SettingsModal, status::StatusModal, ModalMessage};
 use crate::components::icon::*;
+use crate::components::reload_progress::ReloadBadge;
 use crate::contexts::*;
 use crate::pages::MainRoute;
 use crate::utils::render_datetime_with_delta;
@@ -82,6 +83,7 @@ pub fn Header() -> Html {
             <Link<MainRoute> to={MainRoute::Home}><img src="/icon/logo.svg" /></Link<MainRoute>>
             <div>
                 <h1 class="undecorated-link"><Link<MainRoute> to={MainRoute::Home}>{"DeArrow Browser"}</Link<MainRoute>></h1>
+                <ReloadBadge />
                 if !async_tasks_view.tasks.is_empty() {
                     <div id="async-tasks-badge" class="clickable header-badge" onclick={open_async_tasks_modal}>
                         {(*task_badge).clone()}
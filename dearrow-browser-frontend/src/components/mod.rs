@@ -23,3 +23,4 @@ pub mod searchbar;
 pub mod youtube;
 pub mod links;
 pub mod icon;
+pub mod reload_progress;
@@ -22,6 +22,7 @@ use yew::prelude::*;
 use chrono::DateTime;
 use yew_hooks::{use_async, use_interval};
 
+use crate::components::reload_progress::render_reload_details;
 use crate::contexts::{StatusContext, WindowContext};
 use crate::thumbnails::components::{TRExt, Thumbgen, ThumbgenContext, ThumbgenContextExt, ThumbgenRefreshContext};
 use crate::utils::{render_datetime, RenderNumber};
@@ -305,6 +306,10 @@ pub fn StatusModal() -> Html {
                             </td>
                         </tr>
                     </table>
+                    if let Some(ref job) = status.reload {
+                        <h4>{"Last reload"}</h4>
+                        {render_reload_details(job)}
+                    }
                 } else {
                     <em>{"Loading..."}</em>
                 }
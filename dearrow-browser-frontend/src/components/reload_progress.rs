@@ -0,0 +1,172 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use chrono::DateTime;
+use dearrow_browser_api::unsync::{ReloadJobStatus, ReloadOutcome, ReloadPhase, ReloadPhaseProgress};
+use yew::prelude::*;
+
+use crate::components::modals::{status::StatusModal, ModalMessage};
+use crate::contexts::{ModalRendererControls, ReadinessContext, StatusContext};
+use crate::utils::{render_datetime, RenderNumber};
+
+fn phase_name(phase: ReloadPhase) -> &'static str {
+    match phase {
+        ReloadPhase::Thumbnails => "Thumbnails",
+        ReloadPhase::Titles => "Titles",
+        ReloadPhase::Usernames => "Usernames",
+        ReloadPhase::VipUsers => "VIPs",
+        ReloadPhase::SponsorTimes => "SponsorBlock segments",
+        ReloadPhase::Warnings => "Warnings",
+        ReloadPhase::Indexing => "Indexing",
+        ReloadPhase::Swap => "Swapping databases",
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn render_duration(millis: u64) -> String {
+    format!("{:.1}s", millis as f64 / 1000.)
+}
+
+fn render_phase(progress: &ReloadPhaseProgress) -> String {
+    if progress.rows == 0 {
+        render_duration(progress.elapsed_ms)
+    } else {
+        format!("{} rows, {}", progress.rows.render_int(), render_duration(progress.elapsed_ms))
+    }
+}
+
+/// Number of finished phases, out of [`ReloadPhase::ALL`]
+fn finished_phases(job: &ReloadJobStatus) -> usize {
+    job.phases.iter().filter(|p| p.finished).count()
+}
+
+fn progress_bar(job: &ReloadJobStatus) -> Html {
+    html! {
+        <progress max={ReloadPhase::ALL.len().to_string()} value={finished_phases(job).to_string()} />
+    }
+}
+
+/// Header badge, displayed while the server is reloading the database
+#[function_component]
+pub fn ReloadBadge() -> Html {
+    let status: StatusContext = use_context().expect("ReloadBadge should be placed inside a StatusContext provider");
+    let modal_controls: ModalRendererControls = use_context().expect("ReloadBadge should be placed inside a ModalRenderer");
+    let open_status_modal = use_callback(modal_controls, |_, modal_controls| {
+        modal_controls.emit(ModalMessage::Open(html! {<StatusModal />}));
+    });
+
+    let Some(job) = status.as_ref().and_then(|s| s.reload.as_ref()).filter(|job| job.outcome == ReloadOutcome::Running) else {
+        return html! {};
+    };
+    let current = job.phases.last().filter(|p| !p.finished);
+    html! {
+        <div id="reload-badge" class="clickable header-badge" onclick={open_status_modal}>
+            <span>
+                {"Reloading: "}
+                if let Some(phase) = current {
+                    {phase_name(phase.phase)}
+                } else {
+                    {"Starting"}
+                }
+            </span>
+            {progress_bar(job)}
+        </div>
+    }
+}
+
+/// Details of the most recent reload, for the status modal
+pub fn render_reload_details(job: &ReloadJobStatus) -> Html {
+    html! {
+        <table>
+            <tr>
+                <th>{"Job"}</th>
+                <td>{format!("#{}, requested by {}", job.id, job.requested_by)}</td>
+            </tr>
+            <tr>
+                <th>{"Started at"}</th>
+                <td>
+                    if let Some(dt) = DateTime::from_timestamp_millis(job.started) {
+                        {render_datetime(dt)}
+                    } else {
+                        <em>{"Failed to parse"}</em>
+                    }
+                </td>
+            </tr>
+            <tr>
+                <th>{"Outcome"}</th>
+                <td>
+                    {match job.outcome {
+                        ReloadOutcome::Running => html! {progress_bar(job)},
+                        ReloadOutcome::Succeeded => html! {"Succeeded"},
+                        ReloadOutcome::Failed => html! {<b>{"Failed"}</b>},
+                    }}
+                    {" after "}{render_duration(job.elapsed_ms)}
+                </td>
+            </tr>
+            if let Some(ref error) = job.error {
+            <tr>
+                <th>{"Error"}</th>
+                <td><pre>{error}</pre></td>
+            </tr>
+            }
+            {for job.phases.iter().map(|progress| html! {
+            <tr>
+                <th>{phase_name(progress.phase)}</th>
+                <td>
+                    {render_phase(progress)}
+                    if !progress.finished {
+                        <em>{" (in progress)"}</em>
+                    }
+                </td>
+            </tr>
+            })}
+        </table>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ReloadingBannerProps {
+    pub children: Html,
+}
+
+/// Displays a banner instead of the children while the server is loading the database
+#[function_component]
+pub fn ReloadingBanner(props: &ReloadingBannerProps) -> Html {
+    let readiness: ReadinessContext = use_context().expect("ReloadingBanner should be placed inside a ReadinessContext provider");
+    let Some(readiness) = readiness else {
+        return props.children.clone();
+    };
+    let job = readiness.reload.as_ref();
+    html! {
+        <div id="reloading-banner">
+            <h2>{"The database is being loaded"}</h2>
+            if let Some(job) = job.filter(|job| job.outcome == ReloadOutcome::Running) {
+                <p>
+                    if let Some(phase) = job.phases.last().filter(|p| !p.finished) {
+                        {phase_name(phase.phase)}{": "}{render_phase(phase)}
+                    } else {
+                        {"Starting"}
+                    }
+                </p>
+                {progress_bar(job)}
+            } else if job.is_some_and(|job| job.outcome == ReloadOutcome::Failed) {
+                <p><b>{"The last load failed."}</b>{" The database will be available after the next successful reload."}</p>
+            }
+            <h3>{"This page will load automatically once it's ready."}</h3>
+        </div>
+    }
+}
@@ -15,12 +15,98 @@
 *  You should have received a copy of the GNU Affero General Public License
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
+use std::rc::Rc;
+
+use dearrow_browser_api::{unsync::UserSearchResult, USER_SEARCH_MIN_LENGTH};
 use reqwest::Url;
 use web_sys::HtmlInputElement;
+use yew::platform::{spawn_local, time::sleep};
 use yew::prelude::*;
 use yew_router::{hooks::use_navigator, prelude::Link};
 
+use crate::constants::USER_SEARCH_DEBOUNCE;
+use crate::contexts::WindowContext;
 use crate::pages::MainRoute;
+use crate::utils::api_request;
+
+fn is_public_user_id(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[function_component]
+fn UserSearch() -> Html {
+    let navigator = use_navigator().expect("navigator should exist");
+    let window_context: Rc<WindowContext> = use_context().expect("WindowContext should be defined");
+    let suggestions: UseStateHandle<Option<Rc<UserSearchResult>>> = use_state(|| None);
+    let latest_query = use_mut_ref(String::new);
+
+    let oninput = {
+        let suggestions = suggestions.clone();
+        let latest_query = latest_query.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let value = input.value().trim().to_owned();
+            value.clone_into(&mut latest_query.borrow_mut());
+            if value.chars().count() < USER_SEARCH_MIN_LENGTH || is_public_user_id(&value) {
+                suggestions.set(None);
+                return;
+            }
+            let mut url = window_context.origin_join_segments(&["api", "users", "search"]);
+            url.query_pairs_mut().append_pair("query", &value);
+            let suggestions = suggestions.clone();
+            let latest_query = latest_query.clone();
+            spawn_local(async move {
+                // only search once the user stops typing
+                sleep(USER_SEARCH_DEBOUNCE).await;
+                if *latest_query.borrow() != value {
+                    return;
+                }
+                let result = api_request::<_, UserSearchResult>(url).await;
+                // ignore responses to outdated queries
+                if *latest_query.borrow() == value {
+                    suggestions.set(result.ok().map(Rc::new));
+                }
+            });
+        })
+    };
+    let onkeydown = {
+        let suggestions = suggestions.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Enter" {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                let value = input.value().trim().to_owned();
+                // jump straight to the best match if the input isn't a full user ID
+                let id = match *suggestions {
+                    Some(ref result) if !is_public_user_id(&value) => result.users.first().map_or(value, |u| u.user_id.to_string()),
+                    _ => value,
+                };
+                navigator.push(&MainRoute::User { id: id.into() });
+            }
+        })
+    };
+
+    html! {
+        <div>
+            <label for="uid_search">{"Search by User ID or username"}</label>
+            <input id="uid_search" placeholder="User ID or username" list="uid_search_suggestions" {oninput} {onkeydown} />
+            <datalist id="uid_search_suggestions">
+                if let Some(ref result) = *suggestions {
+                    { for result.users.iter().map(|user| {
+                        let collision = user.username.as_ref().is_some_and(|name| result.username_collisions.contains(name));
+                        let label = match user.username {
+                            Some(ref name) if collision => format!("{name} (username shared with other users)"),
+                            Some(ref name) => name.to_string(),
+                            None => "No username set".to_owned(),
+                        };
+                        html! {
+                            <option key={user.user_id.clone()} value={user.user_id.clone()}>{label}</option>
+                        }
+                    }) }
+                }
+            </datalist>
+        </div>
+    }
+}
 
 macro_rules! search_block {
     ($id:expr, $name:expr, $callback:expr) => {
@@ -46,15 +132,6 @@ pub fn Searchbar() -> Html {
             }
         })
     };
-    let uid_search = {
-        let navigator = navigator.clone();
-        Callback::from(move |e: KeyboardEvent| {
-            if e.key() == "Enter" {
-                let input: HtmlInputElement = e.target_unchecked_into();
-                navigator.push(&MainRoute::User {id: input.value().into()});
-            }
-        })
-    };
     let vid_search = { 
         let navigator = navigator.clone();
         Callback::from(move |e: KeyboardEvent| {
@@ -94,7 +171,7 @@ pub fn Searchbar() -> Html {
         <div id="searchbar">
             {search_block!("uuid_search", "UUID", uuid_search)}
             {search_block!("vid_search", "Video ID", vid_search)}
-            {search_block!("uid_search", "User ID", uid_search)}
+            <UserSearch />
             {search_block!("channel_search", "Channel", channel_search)}
             <fieldset>
                 <legend>{"Filtered views"}</legend>
@@ -103,6 +180,12 @@ pub fn Searchbar() -> Html {
                     <li><Link<MainRoute> to={MainRoute::Broken}>{"Broken entries"}</Link<MainRoute>></li>
                 </ul>
             </fieldset>
+            <fieldset>
+                <legend>{"Users"}</legend>
+                <ul>
+                    <li><Link<MainRoute> to={MainRoute::Leaderboard}>{"Leaderboards"}</Link<MainRoute>></li>
+                </ul>
+            </fieldset>
         </div>
     }
 }
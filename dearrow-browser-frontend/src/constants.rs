@@ -22,6 +22,10 @@ use reqwest::{Url, Client};
 pub static REQWEST_CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
 pub static USER_AGENT: LazyLock<&'static str> = LazyLock::new(create_useragent_string);
 pub const ASYNC_TASK_AUTO_DISMISS_DELAY: Duration = Duration::from_secs(15);
+/// How often to check if the database is available again, in milliseconds
+pub const DB_UNAVAILABLE_POLL_INTERVAL: u32 = 5000;
+/// How long to wait for the user to stop typing before searching for users
+pub const USER_SEARCH_DEBOUNCE: Duration = Duration::from_millis(250);
 
 // URLs
 
@@ -17,7 +17,7 @@
 */
 use std::rc::Rc;
 
-use dearrow_browser_api::unsync::{StatusResponse, User};
+use dearrow_browser_api::unsync::{ReadinessResponse, StatusResponse, User};
 use error_handling::ErrorContext;
 use gloo_console::error;
 use reqwest::Url;
@@ -46,6 +46,9 @@ impl WindowContext {
 #[derive(Clone, Copy, PartialEq)]
 pub struct UpdateClock(pub bool);
 
+/// Set while the server responds with 503 Service Unavailable because it is loading the database, along with the progress of the load
+pub type ReadinessContext = Option<Rc<ReadinessResponse>>;
+
 pub type StatusContext = Option<Rc<StatusResponse>>;
 
 #[derive(Clone, PartialEq)]
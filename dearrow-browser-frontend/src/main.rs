@@ -17,16 +17,18 @@
 */
 use std::rc::Rc;
 use components::async_task_manager::AsyncTaskManager;
-use dearrow_browser_api::unsync::StatusResponse;
-use error_handling::ErrorContext;
+use dearrow_browser_api::unsync::{ReadinessResponse, ReloadOutcome, StatusResponse};
+use constants::{DB_UNAVAILABLE_POLL_INTERVAL, REQWEST_CLIENT};
+use error_handling::{bail, ErrorContext, ResContext};
 use gloo_console::error;
 use reqwest::Url;
 use thumbnails::components::ThumbgenProvider;
-use utils::api_request;
+use utils::ReqwestResponseExt;
 use yew::prelude::*;
 use yew_hooks::{use_async_with_options, use_interval, UseAsyncOptions};
 use yew_router::prelude::*;
-use web_sys::window;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{window, Event, EventSource};
 
 pub mod components;
 pub mod constants;
@@ -52,11 +54,31 @@ fn App() -> Html {
         }
     });
     let update_clock = use_state(|| UpdateClock(false));
+    let readiness = use_state(|| ReadinessContext::None);
 
     let status = {
         let status_url = use_memo(window_context.clone(), |wc| wc.origin_join_segments(&["api", "status"]));
+        let ready_url = use_memo(window_context.clone(), |wc| wc.origin_join_segments(&["api", "ready"]));
+        let readiness = readiness.clone();
         use_async_with_options::<_, Rc<StatusResponse>, ErrorContext>(async move { 
-            api_request::<_, StatusResponse>((*status_url).clone()).await
+            let resp = REQWEST_CLIENT.get((*status_url).clone())
+                .header("Accept", "application/json")
+                .send().await.context("Failed to send the request")?;
+            // keep the last status while the database is being loaded, pages show a banner instead
+            if resp.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+                // /api/ready reports the progress of the load, even while the status is unavailable
+                let ready = match REQWEST_CLIENT.get((*ready_url).clone()).header("Accept", "application/json").send().await {
+                    Ok(resp) => resp.json::<ReadinessResponse>().await.ok(),
+                    Err(..) => None,
+                };
+                readiness.set(Some(Rc::new(ready.unwrap_or(ReadinessResponse { ready: false, reload: None }))));
+                bail!("The database is being loaded");
+            }
+            if readiness.is_some() {
+                readiness.set(None);
+            }
+            resp.check_status().await?
+                .json::<StatusResponse>().await.context("Failed to deserialize response")
                 .map(Rc::new)
                 .inspect_err(|err| error!(format!("Failed to fetch status: {err:?}")))
         }, UseAsyncOptions::enable_auto())
@@ -69,6 +91,47 @@ fn App() -> Html {
             status.run();
         }, 60*1000);
     }
+    {
+        // follow the progress of a running reload, and check when the database becomes available again
+        let reloading = status.data.as_ref().and_then(|s| s.reload.as_ref()).is_some_and(|job| job.outcome == ReloadOutcome::Running);
+        let millis = if readiness.is_some() {
+            DB_UNAVAILABLE_POLL_INTERVAL
+        } else if reloading {
+            1000
+        } else {
+            0
+        };
+        let status = status.clone();
+        use_interval(move || status.run(), millis);
+    }
+    {
+        // refresh the status (and with it, all pages) as soon as the server reloads
+        let status = status.clone();
+        let feed_url = use_memo(window_context.clone(), |wc| {
+            let mut url = wc.origin_join_segments(&["api", "feed"]);
+            url.set_query(Some("submissions=false"));
+            url
+        });
+        use_effect_with(feed_url, move |url| {
+            let source = EventSource::new(url.as_str())
+                .inspect_err(|err| error!("Failed to subscribe to the submission feed:", err))
+                .ok();
+            let callback: Closure<dyn Fn(Event)> = Closure::new(move |_: Event| status.run());
+            if let Some(ref source) = source {
+                for event in ["reload_started", "reload_finished", "reload_failed"] {
+                    if let Err(err) = source.add_event_listener_with_callback(event, callback.as_ref().unchecked_ref()) {
+                        error!("Failed to add a submission feed event listener:", err);
+                    }
+                }
+            }
+            move || {
+                if let Some(source) = source {
+                    source.close();
+                }
+                drop(callback);
+            }
+        });
+    }
 
     html! {
         <ContextProvider<Rc<WindowContext>> context={window_context}>
@@ -76,11 +139,13 @@ fn App() -> Html {
         <SettingsProvider>
         <ThumbgenProvider>
         <ContextProvider<UpdateClock> context={*update_clock}>
+        <ContextProvider<ReadinessContext> context={(*readiness).clone()}>
         <AsyncTaskManager>
             <BrowserRouter>
                 <Switch<MainRoute> render={render_main_route} />
             </BrowserRouter>
         </AsyncTaskManager>
+        </ContextProvider<ReadinessContext>>
         </ContextProvider<UpdateClock>>
         </ThumbgenProvider>
         </SettingsProvider>
@@ -32,6 +32,7 @@ use crate::utils::{RcEq, ReqwestResponseExt};
 fn ChannelDetails(props: &ChannelPageProps) -> HtmlResult {
     let window_context: Rc<WindowContext> = use_context().expect("WindowContext should be defined");
     let status: StatusContext = use_context().expect("StatusResponse should be defined");
+    let feed_url = window_context.origin_join_segments(&["api", "atom", "channel", &props.channel]);
     let result: Rc<Result<InnertubeChannel, ErrorContext>> = use_async_suspension(|(channel, _)| async move {
         let url = window_context.origin_join_segments(&["innertube","channel", &channel]);
         loop {
@@ -51,6 +52,7 @@ fn ChannelDetails(props: &ChannelPageProps) -> HtmlResult {
             <>
                 <div>{format!("Channel name: {}", channel.channel_name)}</div>
                 <div>{format!("Videos: {} plain, {} VODs, {} shorts, {} releases; {} total", channel.num_videos, channel.num_vods, channel.num_shorts, channel.num_releases, channel.total_videos)}</div>
+                <div><a href={feed_url.to_string()}>{"Atom feed"}</a></div>
             </>
         },
         Err(ref e) => html! {
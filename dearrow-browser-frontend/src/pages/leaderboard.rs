@@ -0,0 +1,155 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::rc::Rc;
+
+use dearrow_browser_api::unsync::{LeaderboardEntry, Leaderboards};
+use error_handling::ErrorContext;
+use yew::prelude::*;
+use yew_router::prelude::Link;
+
+use crate::components::icon::*;
+use crate::contexts::{StatusContext, WindowContext};
+use crate::hooks::use_async_suspension;
+use crate::pages::MainRoute;
+use crate::utils::{api_request, RenderNumber};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LeaderboardKind {
+    Titles,
+    Thumbnails,
+    Locked,
+    AverageScore,
+    RecentlyActive,
+}
+
+impl LeaderboardKind {
+    fn name(self) -> &'static str {
+        match self {
+            LeaderboardKind::Titles => "Most titles",
+            LeaderboardKind::Thumbnails => "Most thumbnails",
+            LeaderboardKind::Locked => "Most locked submissions",
+            LeaderboardKind::AverageScore => "Highest average score",
+            LeaderboardKind::RecentlyActive => "Most active in the last 30 days",
+        }
+    }
+
+    fn value_name(self) -> &'static str {
+        match self {
+            LeaderboardKind::Titles => "Titles",
+            LeaderboardKind::Thumbnails => "Thumbnails",
+            LeaderboardKind::Locked => "Locked",
+            LeaderboardKind::AverageScore => "Average score",
+            LeaderboardKind::RecentlyActive => "Submissions",
+        }
+    }
+
+    fn render_value(self, entry: &LeaderboardEntry) -> String {
+        match self {
+            LeaderboardKind::Titles => entry.title_count.render_int(),
+            LeaderboardKind::Thumbnails => entry.thumbnail_count.render_int(),
+            LeaderboardKind::Locked => entry.locked_count.render_int(),
+            LeaderboardKind::AverageScore => format!("{:.2}", entry.average_score),
+            LeaderboardKind::RecentlyActive => entry.recent_count.render_int(),
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct LeaderboardTableProps {
+    kind: LeaderboardKind,
+    entries: Rc<[LeaderboardEntry]>,
+}
+
+#[function_component]
+fn LeaderboardTable(props: &LeaderboardTableProps) -> Html {
+    html! {
+        <div class="leaderboard">
+            <h3>{props.kind.name()}</h3>
+            if props.entries.is_empty() {
+                <em>{"Nobody qualifies for this leaderboard"}</em>
+            } else {
+                <table>
+                    <tr class="header">
+                        <th>{"#"}</th>
+                        <th>{"User"}</th>
+                        <th>{props.kind.value_name()}</th>
+                    </tr>
+                    { for props.entries.iter().enumerate().map(|(i, entry)| html! {
+                        <tr key={entry.user_id.clone()}>
+                            <td>{i+1}</td>
+                            <td>
+                                <Link<MainRoute> to={MainRoute::User { id: entry.user_id.clone().into() }}>
+                                    if let Some(ref username) = entry.username {
+                                        {username.clone()}
+                                    } else {
+                                        <span class="monospaced">{entry.user_id.clone()}</span>
+                                    }
+                                </Link<MainRoute>>
+                                if entry.vip {
+                                    {" "}<Icon r#type={IconType::VIP} tooltip="This user is a VIP" />
+                                }
+                            </td>
+                            <td>{props.kind.render_value(entry)}</td>
+                        </tr>
+                    }) }
+                </table>
+            }
+        </div>
+    }
+}
+
+#[function_component]
+fn LeaderboardTables() -> HtmlResult {
+    let window_context: Rc<WindowContext> = use_context().expect("WindowContext should be defined");
+    let status: StatusContext = use_context().expect("StatusResponse should be defined");
+    let url = window_context.origin_join_segments(&["api", "leaderboard"]);
+    let result: Rc<Result<Leaderboards, ErrorContext>> = use_async_suspension(|(url, _)| async move {
+        api_request(url).await
+    }, (url, status.map(|s| s.last_updated)))?;
+
+    Ok(match *result {
+        Ok(ref leaderboards) => html! {
+            <div id="leaderboards">
+                <LeaderboardTable kind={LeaderboardKind::Titles} entries={Rc::from(leaderboards.titles.as_slice())} />
+                <LeaderboardTable kind={LeaderboardKind::Thumbnails} entries={Rc::from(leaderboards.thumbnails.as_slice())} />
+                <LeaderboardTable kind={LeaderboardKind::Locked} entries={Rc::from(leaderboards.locked.as_slice())} />
+                <LeaderboardTable kind={LeaderboardKind::AverageScore} entries={Rc::from(leaderboards.average_score.as_slice())} />
+                <LeaderboardTable kind={LeaderboardKind::RecentlyActive} entries={Rc::from(leaderboards.recently_active.as_slice())} />
+            </div>
+        },
+        Err(ref e) => html! {
+            <div>{"Failed to fetch leaderboards"}<br/><pre>{format!("{e:?}")}</pre></div>
+        },
+    })
+}
+
+#[function_component]
+pub fn LeaderboardPage() -> Html {
+    let fallback = html! {
+        <center><b>{"Loading..."}</b></center>
+    };
+
+    html! {
+        <>
+            <h2>{"User leaderboards"}</h2>
+            <Suspense {fallback}>
+                <LeaderboardTables />
+            </Suspense>
+        </>
+    }
+}
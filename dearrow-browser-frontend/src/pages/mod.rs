@@ -23,10 +23,12 @@ use yew::prelude::*;
 use crate::components::detail_table::DetailType;
 use crate::components::header_footer::*;
 use crate::components::modals::ModalRenderer;
+use crate::components::reload_progress::ReloadingBanner;
 
 mod broken;
 mod channel;
 mod home;
+mod leaderboard;
 mod unverified;
 mod user;
 mod video;
@@ -35,6 +37,7 @@ mod uuid;
 use broken::BrokenPage;
 use channel::ChannelPage;
 use home::HomePage;
+use leaderboard::LeaderboardPage;
 use unverified::UnverifiedPage;
 use user::UserPage;
 use video::VideoPage;
@@ -48,6 +51,8 @@ pub enum MainRoute {
     Unverified,
     #[at("/broken")]
     Broken,
+    #[at("/leaderboard")]
+    Leaderboard,
     #[at("/video_id/:id")]
     Video { id: AttrValue },
     #[at("/channel/:id")]
@@ -77,6 +82,7 @@ pub fn render_main_route(route: MainRoute) -> Html {
         MainRoute::Home => "DeArrow Browser".to_string(),
         MainRoute::Unverified => "Unverified titles - DeArrow Browser".to_string(),
         MainRoute::Broken => "Broken entries - DeArrow Browser".to_string(),
+        MainRoute::Leaderboard => "User leaderboards - DeArrow Browser".to_string(),
         MainRoute::NotFound => "Page not found - DeArrow Browser".to_string(),
         MainRoute::NotImplemented => "Not implemented - DeArrow Browser".to_string(),
         MainRoute::Video { ref id } => format!("VideoID {id} - DeArrow Browser"),
@@ -88,6 +94,7 @@ pub fn render_main_route(route: MainRoute) -> Html {
         MainRoute::Home => html! {<HomePage/>},
         MainRoute::Unverified => html! {<UnverifiedPage/>},
         MainRoute::Broken => html! {<BrokenPage/>},
+        MainRoute::Leaderboard => html! {<LeaderboardPage/>},
         MainRoute::Video { ref id } => html! {<VideoPage videoid={id.clone()} />},
         MainRoute::Channel { ref id } => html! {<ChannelPage channel={id.clone()} />},
         MainRoute::User { ref id } => html! {<UserPage userid={id.clone()} />},
@@ -112,7 +119,7 @@ pub fn render_main_route(route: MainRoute) -> Html {
         <ModalRenderer>
             <Header />
             <div id="content" data-route={route_name}>
-                {route_html}
+                <ReloadingBanner>{route_html}</ReloadingBanner>
             </div>
             <Footer />
         </ModalRenderer>
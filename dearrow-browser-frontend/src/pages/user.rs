@@ -41,6 +41,7 @@ fn UserDetails(props: &UserDetailsProps) -> HtmlResult {
         api_request(url.clone()).await
     }, (url, status.map(|s| s.last_updated)))?;
     let sbb_url: Rc<AttrValue> = use_memo(props.userid.clone(), |uid| AttrValue::Rc(sbb_userid_link(uid).as_str().into()));
+    let feed_url = window_context.origin_join_segments(&["api", "atom", "user_id", &props.userid]);
 
     Ok(match *result {
         Ok(ref user) => html! {
@@ -68,6 +69,7 @@ fn UserDetails(props: &UserDetailsProps) -> HtmlResult {
                 <div>{format!("Titles: {}", user.title_count)}</div>
                 <div>{format!("Thumbnails: {}", user.thumbnail_count)}</div>
                 <div><a href={&*sbb_url}>{"View on SB Browser"}</a></div>
+                <div><a href={feed_url.to_string()}>{"Atom feed"}</a></div>
             </>
         },
         Err(ref e) => html! {
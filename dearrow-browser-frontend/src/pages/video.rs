@@ -18,7 +18,7 @@
 use std::rc::Rc;
 
 use error_handling::{anyhow, ErrContext, ErrorContext, ResContext};
-use dearrow_browser_api::unsync::{InnertubeVideo, Video};
+use dearrow_browser_api::unsync::{BrandingHiddenReason, InnertubeVideo, Video, VideoBranding};
 use gloo_console::error;
 use yew::prelude::*;
 use yew_hooks::{use_async_with_options, UseAsyncHandle, UseAsyncOptions};
@@ -27,7 +27,7 @@ use yew_router::prelude::Link;
 use crate::components::detail_table::*;
 use crate::components::icon::{Icon, IconType};
 use crate::components::youtube::{OriginalTitle, YoutubeIframe};
-use crate::contexts::WindowContext;
+use crate::contexts::{StatusContext, WindowContext};
 use crate::hooks::{use_async_suspension, use_location_state};
 use crate::innertube::{self, youtu_be_link};
 use crate::pages::MainRoute;
@@ -45,6 +45,8 @@ struct VideoDetailsTableProps {
 fn VideoDetailsTable(props: &VideoDetailsTableProps) -> Html {
     let youtube_url: Rc<AttrValue> = use_memo(props.videoid.clone(), |vid| AttrValue::Rc(youtu_be_link(vid).as_str().into()));
     let sbb_url: Rc<AttrValue> = use_memo(props.videoid.clone(), |vid| AttrValue::Rc(sbb_video_link(vid).as_str().into()));
+    let window_context: Rc<WindowContext> = use_context().expect("WindowContext should be defined");
+    let feed_url = window_context.origin_join_segments(&["api", "atom", "video_id", &props.videoid]);
     let fallback = html!{
         <span><em>{"Loading..."}</em></span>
     };
@@ -57,8 +59,9 @@ fn VideoDetailsTable(props: &VideoDetailsTableProps) -> Html {
             </div>
             <div hidden={props.mode != DetailType::Title}>
                 {"Original title: "}
-                <Suspense {fallback}><OriginalTitle videoid={props.videoid.clone()} /></Suspense>
+                <Suspense fallback={fallback.clone()}><OriginalTitle videoid={props.videoid.clone()} /></Suspense>
             </div>
+            <Suspense {fallback}><BrandingExplanation videoid={props.videoid.clone()} mode={props.mode} /></Suspense>
             if props.metadata.loading {
                 <div><em>{"Loading extra metadata..."}</em></div>
             } else if let Some(ref data) = props.metadata.data {
@@ -90,10 +93,100 @@ fn VideoDetailsTable(props: &VideoDetailsTableProps) -> Html {
             
             <div><a href={&*youtube_url}>{"View on YouTube"}</a></div>
             <div><a href={&*sbb_url}>{"View on SB Browser"}</a></div>
+            <div><a href={feed_url.to_string()}>{"Atom feed"}</a></div>
         </div>
     }
 }
 
+fn hidden_reason_text(reason: BrandingHiddenReason) -> &'static str {
+    match reason {
+        BrandingHiddenReason::BelowVoteThreshold => "Score too low",
+        BrandingHiddenReason::Removed => "Removed",
+        BrandingHiddenReason::ShadowHidden => "Shadowhidden",
+        BrandingHiddenReason::MissingVotes => "Missing from the votes table",
+        BrandingHiddenReason::MissingTimestamp => "Missing timestamp",
+        BrandingHiddenReason::UnverifiedNotLocked => "Unverified, needs an upvote",
+        BrandingHiddenReason::Outranked => "Outranked by the displayed submission",
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct BrandingExplanationProps {
+    videoid: AttrValue,
+    mode: DetailType,
+}
+
+/// Shows which submission the extension would display and why the others are hidden
+#[function_component]
+fn BrandingExplanation(props: &BrandingExplanationProps) -> HtmlResult {
+    let window_context: Rc<WindowContext> = use_context().expect("WindowContext should be defined");
+    let status: StatusContext = use_context().expect("StatusResponse should be defined");
+    let url = window_context.origin_join_segments(&["api", "videos", &props.videoid, "branding"]);
+    let result: Rc<Result<VideoBranding, ErrorContext>> = use_async_suspension(|(url, _)| async move {
+        api_request(url).await
+    }, (url, status.map(|s| s.last_updated)))?;
+
+    let Ok(ref branding) = *result else {
+        return Ok(html! {<div><em>{"Failed to fetch branding information."}</em></div>});
+    };
+
+    let (displayed, hidden): (Html, Vec<(AttrValue, String, BrandingHiddenReason)>) = match props.mode {
+        DetailType::Title => (
+            match branding.titles.first() {
+                Some(t) if t.hidden_reason.is_none() => html! {
+                    <Link<MainRoute> to={MainRoute::UUID { id: t.title.uuid.clone().into() }}>{t.title.title.clone()}</Link<MainRoute>>
+                },
+                _ => html! {<em>{"Original title"}</em>},
+            },
+            branding.titles.iter()
+                .filter_map(|t| t.hidden_reason.map(|r| (t.title.uuid.clone().into(), t.title.title.to_string(), r)))
+                .collect(),
+        ),
+        DetailType::Thumbnail => (
+            match branding.thumbnails.first() {
+                Some(t) if t.hidden_reason.is_none() => html! {
+                    <Link<MainRoute> to={MainRoute::UUID { id: t.thumbnail.uuid.clone().into() }}>
+                        if let Some(timestamp) = t.thumbnail.timestamp {
+                            {format!("Timestamp {timestamp}")}
+                        } else {
+                            {"Original thumbnail"}
+                        }
+                    </Link<MainRoute>>
+                },
+                _ => html! {<em>{"Random thumbnail"}</em>},
+            },
+            branding.thumbnails.iter()
+                .filter_map(|t| t.hidden_reason.map(|r| (
+                    t.thumbnail.uuid.clone().into(),
+                    t.thumbnail.timestamp.map_or_else(|| "Original thumbnail".to_owned(), |ts| format!("Timestamp {ts}")),
+                    r,
+                )))
+                .collect(),
+        ),
+    };
+
+    Ok(html! {
+        <>
+            <div title="This is the submission the DeArrow extension would display, according to SponsorBlockServer's branding rules">
+                {"Displayed: "}{displayed}
+            </div>
+            if !hidden.is_empty() {
+                <details>
+                    <summary>{format!("Hidden submissions: {}", hidden.len())}</summary>
+                    <ul>
+                        { for hidden.into_iter().map(|(uuid, text, reason)| html! {
+                            <li key={uuid.as_str()}>
+                                <Link<MainRoute> to={MainRoute::UUID { id: uuid.clone() }}>{text}</Link<MainRoute>>
+                                {format!(" - {}", hidden_reason_text(reason))}
+                            </li>
+                        }) }
+                    </ul>
+                </details>
+            }
+        </>
+    })
+}
+
 #[function_component]
 fn ChannelLink(props: &VideoPageProps) -> HtmlResult {
     let channel_handle = use_async_suspension(|vid| async move {
@@ -18,6 +18,7 @@
 use std::{ops::Deref, rc::Rc, fmt::Write};
 
 use chrono::{DateTime, Utc, NaiveDateTime};
+use dearrow_browser_api::formats::Format;
 use error_handling::{bail, ErrContext, ErrorContext, ResContext, SerializableError};
 use reqwest::Url;
 use sha2::{digest::array::Array, Digest, Sha256};
@@ -262,12 +263,17 @@ where
     U: reqwest::IntoUrl,
     R: serde::de::DeserializeOwned,
 {
-    REQWEST_CLIENT
+    let response = REQWEST_CLIENT
         .get(url)
-        .header("Accept", "application/json")
+        .header("Accept", "application/msgpack, application/json;q=0.9")
         .send().await.context("Failed to send the request")?
-        .check_status().await?
-        .json().await.context("Failed to deserialize response")
+        .check_status().await?;
+    let format = response.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Format::from_mime_type)
+        .unwrap_or(Format::Json);
+    let body = response.bytes().await.context("Failed to receive the response")?;
+    format.decode(&body).context("Failed to deserialize response")
 }
 
 pub fn sbb_video_link(vid: &str) -> Url {
@@ -0,0 +1,261 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{fs::create_dir_all, path::PathBuf, sync::Arc, thread};
+
+use actix_files::{Files, NamedFile};
+use actix_web::{dev::{fn_service, ServiceRequest, ServiceResponse}, guard, web};
+use dearrow_parser::{DearrowDB, StringSet};
+use error_handling::{ErrorContext, ResContext};
+use log::info;
+
+use crate::constants::*;
+use crate::feed::SubmissionFeed;
+use crate::middleware::{self, ResourceVersions};
+use crate::ratelimit::{RateLimitGroup, RateLimiters};
+use crate::reload::ReloadJobs;
+use crate::state::*;
+use crate::webhooks::Webhooks;
+use crate::{innertube, metrics, opengraph, routes, sbserver_emulation, utils, watcher};
+
+/// Wraps a scope in the middlewares shared by all services
+macro_rules! common_middleware {
+    ($scope:expr) => {
+        $scope
+            .wrap(middleware::CustomStatusCodes)
+            .wrap(middleware::Timings)
+            .wrap(middleware::ErrorRepresentation)
+            .wrap(middleware::RequestMetrics)
+    };
+}
+
+/// Configures a [`DeArrowBrowser`]
+pub struct DeArrowBrowserBuilder {
+    config: AppConfig,
+    config_path: Option<PathBuf>,
+    database: Option<(DearrowDB, StringSet, Vec<ErrorContext>)>,
+}
+
+impl DeArrowBrowserBuilder {
+    /// Sets the file the config is re-read from when it's reloaded
+    #[must_use]
+    pub fn config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Serves an already loaded database, instead of loading it from `mirror_path` in the background
+    ///
+    /// The database must be sorted with [`DearrowDB::sort`].
+    #[must_use]
+    pub fn database(mut self, db: DearrowDB, string_set: StringSet, errors: Vec<ErrorContext>) -> Self {
+        self.database = Some((db, string_set, errors));
+        self
+    }
+
+    /// Creates the shared state, starting the initial load and the mirror watcher if enabled
+    ///
+    /// Must be called from within the actix runtime, which webhooks are delivered on.
+    pub fn build(self) -> Result<DeArrowBrowser, ErrorContext> {
+        let config_handle: ConfigState = web::Data::new(ConfigHandle::new(self.config_path, self.config)?);
+        // settings that can't change at runtime are taken from the startup config
+        let config = config_handle.load();
+        {
+            create_dir_all(&config.cache_path).context("Failed to create the cache main directory")?;
+            create_dir_all(config.cache_path.join(FSCACHE_TEMPDIR)).context("Failed to create the cache temporary directory")?;
+            create_dir_all(config.cache_path.join(FSCACHE_PLAYLISTS)).context("Failed to create the cache playlists directory")?;
+            create_dir_all(config.cache_path.join(IT_BROWSE_VIDEOS.cache_dir)).context("Failed to create the channel cache videos directory")?;
+            create_dir_all(config.cache_path.join(IT_BROWSE_LIVE.cache_dir)).context("Failed to create the channel cache vods directory")?;
+            create_dir_all(config.cache_path.join(IT_BROWSE_SHORTS.cache_dir)).context("Failed to create the channel cache shorts directory")?;
+        }
+        let rate_limiters = web::Data::new(RateLimiters::new(&config.rate_limits));
+        let db: DBState = web::Data::new(DatabaseHandle::new(ChannelCache::new(Arc::default(), config_handle.clone().into_inner(), rate_limiters.clone().into_inner())));
+        let feed = web::Data::new(SubmissionFeed::default());
+        let reload_jobs = web::Data::new(ReloadJobs::default());
+        let webhooks = web::Data::new(Webhooks::new(config_handle.clone()));
+        if let Some((database, string_set, errors)) = self.database {
+            let string_set = Arc::new(string_set);
+            let channel_cache = db.new_channel_cache(string_set.clone());
+            db.store(Arc::new(DatabaseState::new(database, string_set, errors, utils::get_mtime(&config.mirror_path.join("titles.csv")), channel_cache)));
+        } else {
+            // serve the frontend and the health checks while the database loads, data routes respond with 503 until then
+            let job = routes::begin_reload(&reload_jobs, "the initial load".to_owned()).expect("No reload should be running before startup");
+            let (db, config, feed, webhooks) = (db.clone(), config.clone(), feed.clone(), webhooks.clone());
+            thread::Builder::new()
+                .name("initial load".to_owned())
+                .spawn(move || {
+                    info!("Loading database...");
+                    if routes::do_reload(&job, &db, &config, &feed, &webhooks).is_ok() {
+                        info!("Database ready!");
+                    }
+                })
+                .context("Failed to spawn the initial load thread")?;
+        }
+        if config.auto_reload.enable {
+            watcher::spawn(db.clone(), config.clone(), feed.clone(), webhooks.clone(), reload_jobs.clone());
+        }
+        Ok(DeArrowBrowser {
+            config: config_handle,
+            db,
+            feed,
+            webhooks,
+            reload_jobs,
+            resource_versions: web::Data::new(ResourceVersions::default()),
+            rate_limiters,
+        })
+    }
+}
+
+/// The shared state of a browser instance
+///
+/// Cheap to clone, all clones share the same state. The `configure_*` methods mount a group of services
+/// in the scope they're called on, for example:
+/// ```ignore
+/// App::new().service(web::scope("/dearrow/api").configure(|cfg| browser.configure_api(cfg)))
+/// ```
+#[derive(Clone)]
+pub struct DeArrowBrowser {
+    config: ConfigState,
+    db: DBState,
+    feed: web::Data<SubmissionFeed>,
+    webhooks: web::Data<Webhooks>,
+    reload_jobs: web::Data<ReloadJobs>,
+    resource_versions: web::Data<ResourceVersions>,
+    rate_limiters: web::Data<RateLimiters>,
+}
+
+impl DeArrowBrowser {
+    pub fn builder(config: AppConfig) -> DeArrowBrowserBuilder {
+        DeArrowBrowserBuilder {
+            config,
+            config_path: None,
+            database: None,
+        }
+    }
+
+    /// The current config, with settings that can't be reloaded kept at their startup values
+    pub fn config(&self) -> Arc<AppConfig> {
+        self.config.load()
+    }
+
+    /// Re-reads the config file, see [`ConfigHandle::reload`]
+    pub fn reload_config(&self) -> Result<Vec<String>, ErrorContext> {
+        self.config.reload()
+    }
+
+    /// The current database snapshot, if it's loaded
+    pub fn database(&self) -> Option<Arc<DatabaseState>> {
+        self.db.try_load()
+    }
+
+    /// Ends all `/api/feed` streams, which would otherwise hold up a graceful shutdown
+    pub fn close_feeds(&self) {
+        self.feed.close();
+    }
+
+    fn app_data(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(self.config.clone())
+            .app_data(self.db.clone())
+            .app_data(self.feed.clone())
+            .app_data(self.webhooks.clone())
+            .app_data(self.reload_jobs.clone())
+            .app_data(self.resource_versions.clone())
+            .app_data(self.rate_limiters.clone());
+    }
+
+    /// Mounts all services at the paths used by the standalone server
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        let config = self.config();
+        cfg.service(web::scope("/api").configure(|cfg| self.configure_api(cfg)));
+        if config.metrics.enable && config.metrics.listen.is_none() {
+            self.app_data(cfg);
+            cfg.service(common_middleware!(web::scope("/metrics"))
+                .route("", web::get().to(metrics::get_metrics_authenticated))
+            );
+        }
+        cfg.service(web::scope("/sbserver").configure(|cfg| self.configure_sbserver(cfg)))
+            .service(web::scope("/innertube").configure(|cfg| self.configure_innertube(cfg)));
+        self.configure_frontend(cfg);
+    }
+
+    /// Mounts the API used by the frontend, including the Atom feeds and the event stream
+    pub fn configure_api(&self, cfg: &mut web::ServiceConfig) {
+        self.app_data(cfg);
+        cfg.service(common_middleware!(web::scope("").wrap(middleware::RateLimited(RateLimitGroup::Api)))
+            .configure(routes::configure(self.config()))
+        );
+    }
+
+    /// Mounts the `SponsorBlockServer` emulation, which responds with an error while disabled in the config
+    pub fn configure_sbserver(&self, cfg: &mut web::ServiceConfig) {
+        self.app_data(cfg);
+        // the emulation can be toggled by reloading the config, requests fall through to the disabled scope while it's off
+        cfg.service(common_middleware!(web::scope("").wrap(middleware::RateLimited(RateLimitGroup::SBServer)))
+                .guard(guard::fn_guard(|ctx| ctx.app_data::<ConfigState>().is_some_and(|config| config.load().enable_sbserver_emulation)))
+                .configure(sbserver_emulation::configure_enabled)
+            )
+            .service(common_middleware!(web::scope(""))
+                .configure(sbserver_emulation::configure_disabled)
+            );
+    }
+
+    /// Mounts the innertube proxy, which responds with an error if innertube is disabled in the config
+    pub fn configure_innertube(&self, cfg: &mut web::ServiceConfig) {
+        self.app_data(cfg);
+        if self.config().innertube.enable {
+            cfg.service(common_middleware!(web::scope("").wrap(middleware::RateLimited(RateLimitGroup::Innertube)))
+                .configure(innertube::configure_enabled)
+            );
+        } else {
+            cfg.service(common_middleware!(web::scope(""))
+                .configure(innertube::configure_disabled)
+            );
+        }
+    }
+
+    /// Mounts the frontend, serving `index.html` for all unknown paths
+    ///
+    /// Video, user and UUID pages get meta tags describing them for link previews.
+    ///
+    /// The frontend expects to be served from the root of the domain, with the API at `/api`.
+    pub fn configure_frontend(&self, cfg: &mut web::ServiceConfig) {
+        self.app_data(cfg);
+        let config = self.config();
+        let static_content_path = config.static_content_path.clone();
+        cfg.service(common_middleware!(web::scope(""))
+            .configure(opengraph::configure)
+            .service(Files::new("/", static_content_path.as_path())
+                .index_file("index.html")
+                .default_handler(fn_service(move |req: ServiceRequest| {
+                    let index_file = static_content_path.join("index.html");
+                    async move {
+                        let (req, _) = req.into_parts();
+                        let file = NamedFile::open_async(index_file.as_path()).await?;
+                        let resp = file.into_response(&req);
+                        Ok(ServiceResponse::new(req, resp))
+                    }
+                }))
+            )
+        );
+    }
+
+    /// Mounts the Prometheus metrics without authentication, for serving them on a separate listener
+    pub fn configure_metrics(&self, cfg: &mut web::ServiceConfig) {
+        self.app_data(cfg);
+        cfg.route("/metrics", web::get().to(metrics::get_metrics));
+    }
+}
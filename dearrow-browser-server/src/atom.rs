@@ -0,0 +1,238 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::HashSet, fmt::Write, sync::Arc};
+
+use actix_web::{get, http::StatusCode, web, Either, HttpRequest, HttpResponse};
+use chrono::{DateTime, SecondsFormat};
+use dearrow_browser_api::sync as api;
+use dearrow_parser::{DearrowDB, Thumbnail, ThumbnailFlags, Title, TitleFlags};
+use error_handling::ResContext;
+
+use crate::constants::*;
+use crate::formats::Negotiated;
+use crate::middleware::{ETagCache, ETagCacheControl};
+use crate::ratelimit::ClientIp;
+use crate::routes::innertube_disabled;
+use crate::state::{AppConfig, ConfigState, DBState, GetChannelOutput};
+use crate::utils::{self, ExtendResponder, ResponderExt};
+
+pub fn configure(app_config: Arc<AppConfig>) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.service(get_recent_feed)
+           .service(get_user_feed)
+           .service(get_video_feed);
+
+        if app_config.innertube.enable {
+            cfg.service(get_channel_feed);
+        } else {
+            cfg.route("/channel/{channel}", web::route().to(innertube_disabled));
+        }
+    }
+}
+
+type AtomResult = utils::Result<HttpResponse>;
+
+/// A submission from the database, borrowed for rendering an `<entry>`
+enum Submission<'a> {
+    Title(&'a Title),
+    Thumbnail(&'a Thumbnail),
+}
+
+impl Submission<'_> {
+    fn time_submitted(&self) -> i64 {
+        match self {
+            Submission::Title(t) => t.time_submitted,
+            Submission::Thumbnail(t) => t.time_submitted,
+        }
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn format_timestamp(millis: i64) -> String {
+    DateTime::from_timestamp_millis(millis)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Collects the newest submissions matching `filter`, titles and thumbnails mixed together
+fn newest_submissions(db: &DearrowDB, filter: impl Fn(&Arc<str>, &Arc<str>) -> bool) -> Vec<Submission<'_>> {
+    let mut submissions: Vec<Submission> = db.titles.iter().rev()
+        .filter(|t| filter(&t.video_id, &t.user_id))
+        .take(ATOM_FEED_MAX_ENTRIES)
+        .map(Submission::Title)
+        .chain(
+            db.thumbnails.iter().rev()
+            .filter(|t| filter(&t.video_id, &t.user_id))
+            .take(ATOM_FEED_MAX_ENTRIES)
+            .map(Submission::Thumbnail)
+        )
+        .collect();
+    submissions.sort_by_key(|s| std::cmp::Reverse(s.time_submitted()));
+    submissions.truncate(ATOM_FEED_MAX_ENTRIES);
+    submissions
+}
+
+struct FeedInfo<'a> {
+    title: &'a str,
+    /// Path of the frontend page showing the same submissions
+    page_path: &'a str,
+}
+
+fn render_entry(out: &mut String, db: &DearrowDB, origin: &str, submission: &Submission) -> std::fmt::Result {
+    let (uuid, video_id, user_id, time_submitted) = match submission {
+        Submission::Title(t) => (&t.uuid, &t.video_id, &t.user_id, t.time_submitted),
+        Submission::Thumbnail(t) => (&t.uuid, &t.video_id, &t.user_id, t.time_submitted),
+    };
+    let author = db.usernames.get(user_id).map_or(&**user_id, |u| &*u.username);
+    let (entry_title, details) = match submission {
+        Submission::Title(t) => (
+            format!("Title: {}", t.title),
+            format!(
+                "Score: {}{}{}{}",
+                t.votes.saturating_sub(t.downvotes),
+                if t.flags.contains(TitleFlags::Locked) { ", locked" } else { "" },
+                if t.flags.contains(TitleFlags::Unverified) { ", unverified" } else { "" },
+                if t.flags.intersects(TitleFlags::Removed | TitleFlags::ShadowHidden) { ", hidden" } else { "" },
+            ),
+        ),
+        Submission::Thumbnail(t) => (
+            match t.timestamp {
+                Some(ts) => format!("Thumbnail at {ts}s"),
+                None => "Original thumbnail".to_owned(),
+            },
+            format!(
+                "Score: {}{}{}",
+                t.votes.saturating_sub(t.downvotes),
+                if t.flags.contains(ThumbnailFlags::Locked) { ", locked" } else { "" },
+                if t.flags.intersects(ThumbnailFlags::Removed | ThumbnailFlags::ShadowHidden) { ", hidden" } else { "" },
+            ),
+        ),
+    };
+    let link = format!("{origin}/uuid/{uuid}");
+    write!(out,
+        "<entry><id>{link}</id><title>{}</title><updated>{updated}</updated><published>{updated}</published>\
+        <author><name>{}</name><uri>{origin}/user_id/{}</uri></author><link rel=\"alternate\" href=\"{link}\"/>\
+        <summary>{}</summary></entry>",
+        escape(&entry_title),
+        escape(author),
+        escape(user_id),
+        escape(&format!("Video {video_id}. {details}")),
+        link = escape(&link),
+        origin = escape(origin),
+        updated = format_timestamp(time_submitted),
+    )
+}
+
+fn render_feed(req: &HttpRequest, config: &ConfigState, db: &DearrowDB, last_updated: i64, info: &FeedInfo, submissions: &[Submission]) -> AtomResult {
+    // the Host header is chosen by the client, and the feeds are cached
+    let config = config.load();
+    let origin = config.public_origin();
+    let self_url = format!("{origin}{}", req.uri());
+    let updated = submissions.first().map_or(last_updated, Submission::time_submitted);
+
+    let mut out = String::from(r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom">"#);
+    write!(out,
+        "<id>{self_url}</id><title>{}</title><updated>{}</updated><generator>DeArrow Browser</generator>\
+        <link rel=\"self\" href=\"{self_url}\"/><link rel=\"alternate\" href=\"{}\"/>",
+        escape(info.title),
+        format_timestamp(updated),
+        escape(&format!("{origin}{}", info.page_path)),
+        self_url = escape(&self_url),
+    ).context("Failed to render the feed header")?;
+    for submission in submissions {
+        render_entry(&mut out, db, origin, submission).context("Failed to render a feed entry")?;
+    }
+    out.push_str("</feed>");
+
+    Ok(HttpResponse::Ok().content_type("application/atom+xml; charset=utf-8").body(out))
+}
+
+#[get("/recent", wrap = "ETagCache")]
+async fn get_recent_feed(req: HttpRequest, config: ConfigState, db_state: DBState) -> AtomResult {
+    let db = db_state.load()?;
+    let submissions = newest_submissions(&db.db, |_, _| true);
+    render_feed(&req, &config, &db.db, db.last_updated, &FeedInfo { title: "Recent DeArrow submissions", page_path: "/" }, &submissions)
+}
+
+#[get("/user_id/{user_id}", wrap = "ETagCache")]
+async fn get_user_feed(req: HttpRequest, config: ConfigState, db_state: DBState, path: web::Path<String>) -> AtomResult {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.as_str()).cloned()
+        .ok_or(utils::Error::EmptyStatus(StatusCode::NOT_FOUND))?;
+    let submissions = newest_submissions(&db.db, |_, uid| Arc::ptr_eq(uid, &user_id));
+    let title = match db.db.usernames.get(&user_id) {
+        Some(u) => format!("DeArrow submissions by {}", u.username),
+        None => format!("DeArrow submissions by {user_id}"),
+    };
+    render_feed(&req, &config, &db.db, db.last_updated, &FeedInfo { title: &title, page_path: &format!("/user_id/{user_id}") }, &submissions)
+}
+
+#[get("/video_id/{video_id}", wrap = "ETagCache")]
+async fn get_video_feed(req: HttpRequest, config: ConfigState, db_state: DBState, path: web::Path<String>) -> AtomResult {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.as_str()).cloned()
+        .ok_or(utils::Error::EmptyStatus(StatusCode::NOT_FOUND))?;
+    let submissions = newest_submissions(&db.db, |vid, _| Arc::ptr_eq(vid, &video_id));
+    render_feed(
+        &req, &config, &db.db, db.last_updated,
+        &FeedInfo { title: &format!("DeArrow submissions for video {video_id}"), page_path: &format!("/video_id/{video_id}") },
+        &submissions,
+    )
+}
+
+#[get("/channel/{channel}", wrap = "ETagCache")]
+async fn get_channel_feed(req: HttpRequest, client: ClientIp, config: ConfigState, db_state: DBState, path: web::Path<String>) -> utils::Result<Either<HttpResponse, (ExtendResponder<Negotiated<api::ChannelFetchProgress>>, StatusCode)>> {
+    let channel_cache = {
+        let db = db_state.load()?;
+        db.channel_cache.clone()
+    };
+    let channel_data = channel_cache.get_channel(path.as_str(), client).await.context("Failed to get channel info")?;
+
+    match channel_data {
+        GetChannelOutput::Pending(progress) => {
+            let mut resp = Negotiated(api::ChannelFetchProgress::from(&progress)).extend();
+            resp.extensions.insert(ETagCacheControl::DoNotCache);
+            Ok(Either::Right((resp, *NOT_READY_YET)))
+        },
+        GetChannelOutput::RateLimited(retry_after) => Err(utils::Error::RateLimited(retry_after)),
+        GetChannelOutput::Resolved(result) => {
+            let vid_set: HashSet<usize> = result.video_ids.iter().map(utils::arc_addr).collect();
+            let db = db_state.load()?;
+            let submissions = newest_submissions(&db.db, |vid, _| vid_set.contains(&utils::arc_addr(vid)));
+            Ok(Either::Left(render_feed(
+                &req, &config, &db.db, db.last_updated,
+                &FeedInfo { title: &format!("DeArrow submissions for channel {}", result.channel_name), page_path: &format!("/channel/{path}") },
+                &submissions,
+            )?))
+        },
+    }
+}
@@ -0,0 +1,73 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use actix_web::{http::{header, StatusCode}, HttpRequest};
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use crate::state::{AppConfig, TokenScope};
+use crate::utils;
+
+/// Name under which the legacy `auth_secret` is reported in logs
+pub const AUTH_SECRET_NAME: &str = "auth_secret";
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Checks the bearer token of the request, returning the name of the matching token if it grants `scope`.
+///
+/// The legacy `auth_secret` grants all scopes. For backwards compatibility, it is also accepted in the `auth` query
+/// parameter, if `legacy_query` is given.
+pub fn authorize<'a>(req: &HttpRequest, config: &'a AppConfig, scope: TokenScope, legacy_query: Option<&str>) -> utils::Result<&'a str> {
+    let bearer = req.headers().get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    let provided = match (bearer, legacy_query) {
+        (Some(token), _) => token,
+        (None, Some(secret)) => {
+            warn!("The auth secret was passed in the URL, where it may be logged. Use the Authorization header instead.");
+            if config.auth_secret.is_empty() || !constant_time_eq(&Sha256::digest(secret), &Sha256::digest(config.auth_secret.as_str())) {
+                return Err(utils::Error::EmptyStatus(StatusCode::FORBIDDEN));
+            }
+            return Ok(AUTH_SECRET_NAME);
+        },
+        (None, None) => return Err(utils::Error::EmptyStatus(StatusCode::NOT_FOUND)),
+    };
+
+    // hash first, so that the comparison time doesn't depend on the token lengths,
+    // and check every token, so that it doesn't depend on which one matched
+    let provided = Sha256::digest(provided);
+    let mut matched = None;
+    // empty secrets are rejected when loading the config, but never let them match an empty header either
+    if !config.auth_secret.is_empty() && constant_time_eq(&provided, &Sha256::digest(config.auth_secret.as_str())) {
+        matched = Some((AUTH_SECRET_NAME, true));
+    }
+    for token in config.tokens.iter().filter(|t| !t.token.is_empty()) {
+        if constant_time_eq(&provided, &Sha256::digest(token.token.as_str())) {
+            matched = Some((token.name.as_str(), token.scopes.contains(&scope)));
+        }
+    }
+    match matched {
+        Some((name, true)) => Ok(name),
+        Some((name, false)) => {
+            warn!("Token '{name}' was used for an action outside of its scopes ({scope:?})");
+            Err(utils::Error::EmptyStatus(StatusCode::FORBIDDEN))
+        },
+        None => Err(utils::Error::EmptyStatus(StatusCode::FORBIDDEN)),
+    }
+}
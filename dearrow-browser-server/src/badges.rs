@@ -0,0 +1,134 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use dearrow_parser::{ThumbnailFlags, TitleFlags};
+use serde::Deserialize;
+
+use crate::atom::escape;
+use crate::constants::*;
+use crate::middleware::ETagCache;
+use crate::sbserver_emulation::top_title;
+use crate::state::DBState;
+use crate::utils;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_user_badge)
+       .service(get_video_badge);
+}
+
+type BadgeResult = utils::Result<HttpResponse>;
+
+const COLOR_LABEL: &str = "#555";
+const COLOR_VALUE: &str = "#007ec6";
+const COLOR_POSITIVE: &str = "#4c1";
+const COLOR_NEUTRAL: &str = "#9f9f9f";
+
+/// Approximate width of the text in pixels, when rendered in 11px Verdana
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| match c {
+        'i' | 'j' | 'l' | 'I' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 4,
+        'f' | 'r' | 't' | ' ' | '(' | ')' | '[' | ']' | '-' => 5,
+        'm' | 'w' | 'M' | 'W' => 11,
+        'A'..='Z' => 8,
+        // most characters of CJK scripts and later blocks are full-width
+        c if c >= '\u{2E80}' => 12,
+        _ => 7,
+    }).sum()
+}
+
+/// Renders a two-part badge, in the style of shields.io
+fn render_badge(label: &str, value: &str, color: &str) -> HttpResponse {
+    let label_width = text_width(label) + 10;
+    let value_width = text_width(value) + 10;
+    let width = label_width + value_width;
+    let (label, value) = (escape(label), escape(value));
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" aria-label=\"{label}: {value}\">\
+        <title>{label}: {value}</title>\
+        <linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+        <clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+        <g clip-path=\"url(#r)\"><rect width=\"{label_width}\" height=\"20\" fill=\"{COLOR_LABEL}\"/><rect x=\"{label_width}\" width=\"{value_width}\" height=\"20\" fill=\"{color}\"/><rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/></g>\
+        <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+        <text x=\"{}\" y=\"14\">{label}</text><text x=\"{}\" y=\"14\">{value}</text></g></svg>",
+        label_width / 2,
+        label_width + value_width / 2,
+    );
+    HttpResponse::Ok().content_type("image/svg+xml; charset=utf-8").body(svg)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum UserBadge {
+    Titles,
+    Thumbnails,
+    Vip,
+    Locked,
+}
+
+#[get("/user_id/{user_id}/{badge}", wrap = "ETagCache")]
+async fn get_user_badge(db_state: DBState, path: web::Path<(String, UserBadge)>) -> BadgeResult {
+    let db = db_state.load()?;
+    let (user_id, badge) = path.into_inner();
+    // users without submissions get badges with zero counts, instead of a broken image
+    let user_id = db.string_set.set.get(user_id.as_str()).cloned();
+    let is_user = |uid: &Arc<str>| user_id.as_ref().is_some_and(|u| Arc::ptr_eq(u, uid));
+    Ok(match badge {
+        UserBadge::Titles => {
+            let count = db.db.titles.iter().filter(|t| is_user(&t.user_id)).count();
+            render_badge("DeArrow titles", &count.to_string(), COLOR_VALUE)
+        },
+        UserBadge::Thumbnails => {
+            let count = db.db.thumbnails.iter().filter(|t| is_user(&t.user_id)).count();
+            render_badge("DeArrow thumbnails", &count.to_string(), COLOR_VALUE)
+        },
+        UserBadge::Vip => {
+            if user_id.is_some_and(|u| db.db.vip_users.contains(&u)) {
+                render_badge("DeArrow", "VIP", COLOR_POSITIVE)
+            } else {
+                render_badge("DeArrow", "not VIP", COLOR_NEUTRAL)
+            }
+        },
+        UserBadge::Locked => {
+            let count = db.db.titles.iter().filter(|t| is_user(&t.user_id) && t.flags.contains(TitleFlags::Locked)).count()
+                + db.db.thumbnails.iter().filter(|t| is_user(&t.user_id) && t.flags.contains(ThumbnailFlags::Locked)).count();
+            render_badge("DeArrow locked submissions", &count.to_string(), COLOR_VALUE)
+        },
+    })
+}
+
+#[get("/video_id/{video_id}/title", wrap = "ETagCache")]
+async fn get_video_badge(db_state: DBState, path: web::Path<String>) -> BadgeResult {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.as_str()).cloned();
+    let top_title = video_id.and_then(|video_id| top_title(&db.db, &video_id));
+    Ok(match top_title {
+        None => render_badge("DeArrow title", "none", COLOR_NEUTRAL),
+        Some(title) => {
+            let text = if title.title.chars().count() > BADGE_TITLE_MAX_CHARS {
+                let mut truncated: String = title.title.chars().take(BADGE_TITLE_MAX_CHARS - 1).collect();
+                truncated.push('…');
+                truncated
+            } else {
+                title.title.to_string()
+            };
+            render_badge("DeArrow title", &text, COLOR_POSITIVE)
+        },
+    })
+}
@@ -0,0 +1,157 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{path::{Path, PathBuf}, time::Instant};
+
+use clap::{Parser, Subcommand};
+use dearrow_parser::{DBPaths, DearrowDB, StringSet};
+use error_handling::{bail, ErrorContext, ResContext};
+
+use crate::constants::*;
+use crate::state::AppConfig;
+use crate::{sqlite_export, tls};
+
+#[derive(Parser)]
+#[command(version, about = "The DeArrow Browser server")]
+pub struct Cli {
+    /// Path to the config file
+    #[arg(long, short, global = true, env = ENV_CONFIG_PATH, default_value = CONFIG_PATH)]
+    pub config: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Default)]
+pub enum Command {
+    /// Run the server (default)
+    #[default]
+    Serve,
+    /// Validate the config file and the paths it points to
+    CheckConfig,
+    /// Load the mirror once and print statistics of the database
+    LoadTest,
+    /// Load the mirror once and write the database to an sqlite file
+    ExportSqlite {
+        /// Path of the sqlite file, replaced if it already exists
+        output: PathBuf,
+    },
+    /// Print the default config file
+    PrintDefaultConfig,
+}
+
+/// Reports every problem in the config, expecting it to be read with [`AppConfig::read`]
+pub fn check_config(config: &AppConfig) -> Result<(), ErrorContext> {
+    let mut problems = config.problems();
+    if config.mirror_path.is_dir() {
+        problems.extend(DBPaths::in_dir(&config.mirror_path).all().into_iter()
+            .filter(|path| !path.is_file())
+            .map(|path| format!("{} is missing from the mirror", path.display()))
+        );
+    } else {
+        problems.push(format!("mirror_path {} is not a directory", config.mirror_path.display()));
+    }
+    if !config.static_content_path.join("index.html").is_file() {
+        problems.push(format!("static_content_path {} does not contain an index.html file", config.static_content_path.display()));
+    }
+    if config.cache_path.exists() && !config.cache_path.is_dir() {
+        problems.push(format!("cache_path {} is not a directory", config.cache_path.display()));
+    }
+    for (i, token) in config.tokens.iter().enumerate() {
+        if config.tokens[..i].iter().any(|t| t.name == token.name) {
+            problems.push(format!("token name '{}' is used more than once", token.name));
+        }
+    }
+    for (i, webhook) in config.webhooks.iter().enumerate() {
+        if let Err(e) = reqwest::Url::parse(&webhook.url) {
+            problems.push(format!("webhook '{}' has an invalid url: {e}", webhook.name));
+        }
+        if !webhook.channels.is_empty() && !config.innertube.enable {
+            problems.push(format!("webhook '{}' filters by channel, which requires innertube to be enabled", webhook.name));
+        }
+        if config.webhooks[..i].iter().any(|w| w.name == webhook.name) {
+            problems.push(format!("webhook name '{}' is used more than once", webhook.name));
+        }
+    }
+    if config.low_memory_reload && !config.webhooks.is_empty() {
+        problems.push("webhooks are configured, but are never sent with low_memory_reload enabled".to_owned());
+    }
+    if config.low_memory_reload && config.sqlite_export {
+        problems.push("sqlite_export is enabled, but the export is skipped with low_memory_reload enabled".to_owned());
+    }
+    if let Some(ref tls) = config.listen.tls {
+        if let Err(e) = tls::load_certified_key(tls) {
+            problems.push(format!("the TLS certificate could not be loaded: {e:?}"));
+        }
+    }
+
+    if problems.is_empty() {
+        println!("The config is valid");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("- {problem}");
+    }
+    bail!("Found {} problem(s) in the config", problems.len());
+}
+
+pub fn load_test(config: &AppConfig) -> Result<(), ErrorContext> {
+    let start = Instant::now();
+    let mut string_set = StringSet::with_capacity(16384);
+    let (mut db, errors) = DearrowDB::load_dir(&config.mirror_path, &mut string_set).context("Failed to load the mirror")?;
+    let loaded = start.elapsed();
+    let sort_start = Instant::now();
+    db.sort();
+    string_set.clean();
+    let video_infos: usize = db.video_infos.iter().map(|chunk| chunk.len()).sum();
+    let uncut_segments: usize = db.video_infos.iter().map(|chunk| chunk.iter().map(|v| v.uncut_segments.len()).sum::<usize>()).sum();
+
+    println!("Loaded in {:.2}s, sorted in {:.2}s", loaded.as_secs_f64(), sort_start.elapsed().as_secs_f64());
+    println!("Titles:         {}", db.titles.len());
+    println!("Thumbnails:     {}", db.thumbnails.len());
+    println!("Usernames:      {}", db.usernames.len());
+    println!("VIPs:           {}", db.vip_users.len());
+    println!("Warnings:       {}", db.warnings.len());
+    println!("Video infos:    {video_infos}");
+    println!("Uncut segments: {uncut_segments}");
+    println!("Strings:        {}", string_set.set.len());
+    println!("Errors:         {}", errors.len());
+    for error in &errors {
+        println!("{error:?}");
+    }
+    Ok(())
+}
+
+pub fn export_sqlite(config: &AppConfig, output: &Path) -> Result<(), ErrorContext> {
+    let start = Instant::now();
+    let mut string_set = StringSet::with_capacity(16384);
+    let (mut db, errors) = DearrowDB::load_dir(&config.mirror_path, &mut string_set).context("Failed to load the mirror")?;
+    db.sort();
+    for error in &errors {
+        eprintln!("{error:?}");
+    }
+    println!("Loaded in {:.2}s", start.elapsed().as_secs_f64());
+    let export_start = Instant::now();
+    sqlite_export::export(&db, output)?;
+    println!("Exported to {} in {:.2}s", output.display(), export_start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+pub fn print_default_config() -> Result<(), ErrorContext> {
+    let serialized = toml::to_string(&AppConfig::default()).context("Failed to serialize default AppConfig as TOML")?;
+    print!("{serialized}");
+    Ok(())
+}
@@ -17,7 +17,6 @@
 */
 use std::{sync::{Arc, LazyLock}, time::Duration};
 use chrono::DateTime;
-use error_handling::{ErrorContext, anyhow};
 use regex::Regex;
 use actix_web::http::StatusCode;
 
@@ -29,16 +28,67 @@ pub static NUMBER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+").
 pub const CONFIG_PATH: &str = "config.toml";
 pub const FSCACHE_TEMPDIR: &str = "tmp";
 pub const FSCACHE_PLAYLISTS: &str = "playlists";
+pub const SQLITE_EXPORT_FILE: &str = "dearrow.sqlite3";
+
+// Config reloading
+/// Settings applied when the config is reloaded at runtime, as dotted paths in the config file.
+/// Changes to all other settings require a restart.
+pub const RUNTIME_SETTINGS: &[&str] = &[
+    "auth_secret",
+    "tokens",
+    "webhooks",
+    "enable_sbserver_emulation",
+    "enable_timings_header",
+    "reqwest_timeout_secs",
+    "innertube.visitor_data",
+    "innertube.po_token",
+];
+
+// Environment variables
+pub const ENV_CONFIG_PATH: &str = "DEARROW_BROWSER_CONFIG";
+pub const ENV_AUTH_SECRET: &str = "DEARROW_BROWSER_AUTH_SECRET";
+pub const ENV_VISITOR_DATA: &str = "DEARROW_BROWSER_INNERTUBE_VISITOR_DATA";
+pub const ENV_PO_TOKEN: &str = "DEARROW_BROWSER_INNERTUBE_PO_TOKEN";
+/// Followed by the name of an admin token, uppercased, with other characters than letters and digits replaced by `_`
+pub const ENV_TOKEN_PREFIX: &str = "DEARROW_BROWSER_TOKEN_";
+/// Followed by the name of a webhook, in the same format as [`ENV_TOKEN_PREFIX`]
+pub const ENV_WEBHOOK_PREFIX: &str = "DEARROW_BROWSER_WEBHOOK_";
+pub const ENV_LISTEN_PID: &str = "LISTEN_PID";
+pub const ENV_LISTEN_FDS: &str = "LISTEN_FDS";
 
 // Limits
 pub static IT_TIMEOUT: Duration = Duration::from_secs(1);
 pub static FSCACHE_SIZE_CACHE_DURATION: Duration = Duration::from_secs(60);
-
-// Locking errors
-pub static SS_READ_ERR:  LazyLock<ErrorContext> = LazyLock::new(|| anyhow!("Failed to acquire StringSet for reading"));
-pub static SS_WRITE_ERR: LazyLock<ErrorContext> = LazyLock::new(|| anyhow!("Failed to acquire StringSet for writing"));
-pub static DB_READ_ERR:  LazyLock<ErrorContext> = LazyLock::new(|| anyhow!("Failed to acquire DatabaseState for reading"));
-pub static DB_WRITE_ERR: LazyLock<ErrorContext> = LazyLock::new(|| anyhow!("Failed to acquire DatabaseState for writing"));
+pub const LEADERBOARD_MAX_COUNT: usize = 100;
+/// Users with fewer submissions than this are not ranked by average score
+pub const LEADERBOARD_AVERAGE_MIN_SUBMISSIONS: u64 = 10;
+pub const LEADERBOARD_RECENT_WINDOW_MS: i64 = 30*24*60*60*1000;
+pub const USER_SEARCH_MAX_COUNT: usize = 100;
+pub use dearrow_browser_api::USER_SEARCH_MIN_LENGTH;
+/// Number of events buffered for each submission feed subscriber
+pub const FEED_CAPACITY: usize = 4096;
+pub static FEED_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// How often feed subscribers filtering by channel retry refreshing the channel's videos after a reload
+pub static FEED_CHANNEL_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+pub const ATOM_FEED_MAX_ENTRIES: usize = 50;
+/// Longer titles are truncated on badges
+pub const BADGE_TITLE_MAX_CHARS: usize = 60;
+/// Rate limiters drop buckets of clients who haven't made requests recently once they track this many clients
+pub const RATELIMIT_PRUNE_THRESHOLD: usize = 16384;
+/// `Retry-After` value sent to rate limited clients if the time until their next request can't be represented
+pub const RATELIMIT_FALLBACK_RETRY_AFTER: Duration = Duration::from_hours(1);
+/// The `ETag` middleware forgets when resources were last modified once it tracks this many of them
+pub const RESOURCE_VERSIONS_LIMIT: usize = 65536;
+/// Value of the `Retry-After` header sent while the database is being loaded
+pub const DB_UNAVAILABLE_RETRY_AFTER: Duration = Duration::from_secs(10);
+/// Delay before the first retry of a failed webhook request, doubled after every further attempt
+pub static WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How often channels matched by webhooks are checked while they're being fetched
+pub static WEBHOOK_CHANNEL_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Channels still being fetched after this many checks are skipped
+pub const WEBHOOK_CHANNEL_MAX_POLLS: u32 = 150;
+/// Upper bounds of the request latency histogram buckets, in seconds
+pub const METRICS_LATENCY_BUCKETS: [f64; 11] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5];
 
 // Innertube API urls
 pub static IT_PLAYER_URL: LazyLock<reqwest::Url> = LazyLock::new(|| reqwest::Url::parse("https://www.youtube.com/youtubei/v1/player").expect("Should be able to parse the IT_PLAYER_URL"));
@@ -65,6 +115,10 @@ pub static SERVER_VERSION:  LazyLock<Arc<str>> = LazyLock::new(|| built_info::PK
 pub static SERVER_GIT_HASH: LazyLock<Option<Arc<str>>> = LazyLock::new(|| built_info::GIT_COMMIT_HASH.map(std::convert::Into::into));
 pub static BUILD_TIMESTAMP: LazyLock<Option<i64>> = LazyLock::new(|| DateTime::parse_from_rfc2822(built_info::BUILT_TIME_UTC).ok().map(|t| t.timestamp()));
 
+// Custom headers
+/// HMAC-SHA256 of the webhook request body, hex encoded and prefixed with `sha256=`
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-DeArrow-Browser-Signature";
+
 // Custom response status codes
 /// 333 Not ready yet - Indicates that the server is still querying requested data.
 /// The response may contain progress information. The client should request the same URL again.
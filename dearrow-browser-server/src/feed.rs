@@ -0,0 +1,263 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::HashSet, convert::Infallible, sync::Arc};
+
+use actix_web::{rt::time::{sleep_until, Instant}, web::Bytes};
+use dearrow_browser_api::sync::{self as api, ApiThumbnail, ApiTitle, IntoWithDatabase};
+use dearrow_parser::DearrowDB;
+use error_handling::ErrorContext;
+use futures::{select_biased, stream, FutureExt, Stream};
+use log::warn;
+use serde::Serialize;
+use tokio::sync::{broadcast::{self, error::RecvError}, watch};
+
+use crate::{constants::*, ratelimit::ClientIp, state::{DBState, GetChannelOutput}};
+
+#[derive(Clone)]
+pub enum FeedEvent {
+    ReloadStarted(api::ReloadStartedEvent),
+    ReloadFinished(api::ReloadFinishedEvent),
+    ReloadFailed(api::ReloadFailedEvent),
+    Title(ApiTitle),
+    Thumbnail(ApiThumbnail),
+    /// Submissions found by a reload, sent as a single event so that large reloads
+    /// don't push the reload events out of the subscribers' buffers
+    Batch(Arc<[FeedEvent]>),
+}
+
+fn encode_event<T: Serialize>(name: &str, data: &T) -> Bytes {
+    let data = serde_json::to_string(data).expect("Feed events should be serializable");
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+impl FeedEvent {
+    fn encode(&self) -> Bytes {
+        match self {
+            FeedEvent::ReloadStarted(ref ev) => encode_event("reload_started", ev),
+            FeedEvent::ReloadFinished(ref ev) => encode_event("reload_finished", ev),
+            FeedEvent::ReloadFailed(ref ev) => encode_event("reload_failed", ev),
+            FeedEvent::Title(ref title) => encode_event("title", title),
+            FeedEvent::Thumbnail(ref thumb) => encode_event("thumbnail", thumb),
+            FeedEvent::Batch(..) => unreachable!("Batches should be split by the subscriber"),
+        }
+    }
+}
+
+/// Broadcasts reload events and newly found submissions to all subscribers
+pub struct SubmissionFeed {
+    sender: broadcast::Sender<FeedEvent>,
+    /// Set once the server starts shutting down
+    shutdown: watch::Sender<bool>,
+}
+
+impl Default for SubmissionFeed {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::Sender::new(FEED_CAPACITY),
+            shutdown: watch::Sender::new(false),
+        }
+    }
+}
+
+impl SubmissionFeed {
+    pub fn send(&self, event: FeedEvent) {
+        // only fails if there are no subscribers
+        let _ = self.sender.send(event);
+    }
+
+    /// Ends all current and future streams, so that they don't hold up a graceful shutdown
+    pub fn close(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Sends the `reload_finished` event, followed by a batch of all submissions present in `new_db`,
+    /// but missing from `old_db`
+    ///
+    /// Only the `reload_finished` event is sent if the old database was already dropped.
+    pub fn send_reload_finished(&self, last_updated: i64, old_db: Option<&DearrowDB>, new_db: &DearrowDB) {
+        let Some(old_db) = old_db else {
+            self.send(FeedEvent::ReloadFinished(api::ReloadFinishedEvent { last_updated, new_titles: 0, new_thumbnails: 0 }));
+            return;
+        };
+        let old_titles: HashSet<&str> = old_db.titles.iter().map(|t| &*t.uuid).collect();
+        let old_thumbs: HashSet<&str> = old_db.thumbnails.iter().map(|t| &*t.uuid).collect();
+        let new_titles: Vec<_> = new_db.titles.iter().filter(|t| !old_titles.contains(&*t.uuid)).collect();
+        let new_thumbs: Vec<_> = new_db.thumbnails.iter().filter(|t| !old_thumbs.contains(&*t.uuid)).collect();
+        self.send(FeedEvent::ReloadFinished(api::ReloadFinishedEvent {
+            last_updated,
+            new_titles: new_titles.len() as u64,
+            new_thumbnails: new_thumbs.len() as u64,
+        }));
+        let batch: Arc<[FeedEvent]> = new_titles.into_iter().map(|t| FeedEvent::Title(t.into_with_db(new_db)))
+            .chain(new_thumbs.into_iter().map(|t| FeedEvent::Thumbnail(t.into_with_db(new_db))))
+            .collect();
+        if !batch.is_empty() {
+            self.send(FeedEvent::Batch(batch));
+        }
+    }
+
+    pub fn subscribe(&self, filter: FeedFilter, db_state: DBState) -> impl Stream<Item = Result<Bytes, Infallible>> {
+        let subscriber = FeedSubscriber {
+            receiver: self.sender.subscribe(),
+            shutdown: self.shutdown.subscribe(),
+            filter,
+            db_state,
+            refresh_channel: false,
+            batch: None,
+            next_keepalive: Instant::now() + FEED_KEEPALIVE_INTERVAL,
+        };
+        stream::unfold(subscriber, |mut sub| async move {
+            sub.next_chunk().await.map(|chunk| (Ok(chunk), sub))
+        })
+    }
+}
+
+pub struct ChannelFilter {
+    pub channel: String,
+    /// Compared by value, since video IDs may come from different `StringSet`s
+    pub video_ids: HashSet<Arc<str>>,
+}
+
+pub struct FeedFilter {
+    pub video_id: Option<String>,
+    pub user_id: Option<String>,
+    pub channel: Option<ChannelFilter>,
+    /// Whether to include new submissions, or only reload events
+    pub submissions: bool,
+}
+
+impl FeedFilter {
+    fn matches_submission(&self, video_id: &Arc<str>, user_id: &str) -> bool {
+        self.submissions
+        && self.video_id.as_ref().is_none_or(|v| **v == **video_id)
+        && self.user_id.as_ref().is_none_or(|u| u == user_id)
+        && self.channel.as_ref().is_none_or(|c| c.video_ids.contains(video_id))
+    }
+
+    fn matches(&self, event: &FeedEvent) -> bool {
+        match event {
+            FeedEvent::ReloadStarted(..) | FeedEvent::ReloadFinished(..) | FeedEvent::ReloadFailed(..) => true,
+            FeedEvent::Title(ref title) => self.matches_submission(&title.video_id, &title.user_id),
+            FeedEvent::Thumbnail(ref thumb) => self.matches_submission(&thumb.video_id, &thumb.user_id),
+            // batches are split by the subscriber, which matches their events one by one
+            FeedEvent::Batch(..) => self.submissions,
+        }
+    }
+}
+
+struct FeedSubscriber {
+    receiver: broadcast::Receiver<FeedEvent>,
+    shutdown: watch::Receiver<bool>,
+    filter: FeedFilter,
+    db_state: DBState,
+    /// Set after a reload, the channel cache is reset then, so the list of videos has to be refetched
+    refresh_channel: bool,
+    /// Batch currently being sent, with the index of its next event
+    batch: Option<(Arc<[FeedEvent]>, usize)>,
+    next_keepalive: Instant,
+}
+
+impl FeedSubscriber {
+    /// Returns `Ok(false)` if the channel is still being fetched
+    async fn refresh_channel_filter(&mut self) -> Result<bool, ErrorContext> {
+        let Some(ref mut filter) = self.filter.channel else { return Ok(true) };
+        let channel_cache = {
+            let Some(db) = self.db_state.try_load() else { return Ok(false) };
+            db.channel_cache.clone()
+        };
+        // refreshes aren't started by the client, don't count them towards its rate limit
+        match channel_cache.get_channel(&filter.channel, ClientIp(None)).await? {
+            GetChannelOutput::Pending(..) | GetChannelOutput::RateLimited(..) => Ok(false),
+            GetChannelOutput::Resolved(data) => {
+                filter.video_ids = data.video_ids.iter().cloned().collect();
+                Ok(true)
+            },
+        }
+    }
+
+    fn keepalive(&mut self) -> Bytes {
+        self.next_keepalive = Instant::now() + FEED_KEEPALIVE_INTERVAL;
+        Bytes::from_static(b": keepalive\n\n")
+    }
+
+    async fn next_chunk(&mut self) -> Option<Bytes> {
+        loop {
+            if *self.shutdown.borrow() {
+                return None;
+            }
+            if self.refresh_channel {
+                match self.refresh_channel_filter().await {
+                    Ok(false) => {
+                        if Instant::now() >= self.next_keepalive {
+                            return Some(self.keepalive());
+                        }
+                        // the channel is still being fetched or the database is being reloaded, retry later
+                        let retry_at = (Instant::now() + FEED_CHANNEL_RETRY_INTERVAL).min(self.next_keepalive);
+                        select_biased! {
+                            changed = self.shutdown.changed().fuse() => if changed.is_err() { return None },
+                            () = sleep_until(retry_at).fuse() => {},
+                        }
+                        continue;
+                    },
+                    Ok(true) => {},
+                    Err(e) => warn!("Failed to refresh the channel filter of a feed subscriber: {e:?}"),
+                }
+                self.refresh_channel = false;
+            }
+            if let Some((ref batch, ref mut next)) = self.batch {
+                let matching = batch[*next..].iter().position(|event| self.filter.matches(event));
+                if let Some(offset) = matching {
+                    let event = &batch[*next + offset];
+                    *next += offset + 1;
+                    self.next_keepalive = Instant::now() + FEED_KEEPALIVE_INTERVAL;
+                    return Some(event.encode());
+                }
+                self.batch = None;
+            }
+            let result = select_biased! {
+                // only errors if the feed was dropped
+                changed = self.shutdown.changed().fuse() => if changed.is_ok() { continue } else { return None },
+                res = self.receiver.recv().fuse() => res,
+                () = sleep_until(self.next_keepalive).fuse() => return Some(self.keepalive()),
+            };
+            match result {
+                Ok(event) => {
+                    if let FeedEvent::ReloadFinished(..) = event {
+                        self.refresh_channel = self.filter.channel.is_some();
+                    }
+                    if let FeedEvent::Batch(batch) = event {
+                        if self.filter.submissions {
+                            self.batch = Some((batch, 0));
+                        }
+                        continue;
+                    }
+                    if self.filter.matches(&event) {
+                        self.next_keepalive = Instant::now() + FEED_KEEPALIVE_INTERVAL;
+                        return Some(event.encode());
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => return Some(encode_event("lagged", &api::FeedLaggedEvent { skipped })),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
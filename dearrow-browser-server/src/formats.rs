@@ -0,0 +1,99 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use actix_web::{body::BoxBody, http::{header::{self, Accept, Header}, StatusCode}, HttpRequest, HttpResponse, Responder, ResponseError};
+use dearrow_browser_api::formats::Format;
+use error_handling::ResContext;
+use serde::Serialize;
+
+use crate::utils;
+
+/// Picks the most preferred format from the `Accept` header, falling back to JSON
+pub fn preferred_format(req: &HttpRequest) -> Format {
+    let Ok(accept) = Accept::parse(req) else { return Format::Json };
+    accept.ranked().iter()
+        .find_map(|mime| match mime.essence_str() {
+            "*/*" | "application/*" => Some(Format::Json),
+            essence => Format::from_mime_type(essence),
+        })
+        .unwrap_or(Format::Json)
+}
+
+/// Like [`actix_web::web::Json`], but encoded in the format requested by the client
+pub struct Negotiated<T>(pub T);
+
+impl<T: Serialize> Negotiated<T> {
+    pub fn into_response(self, req: &HttpRequest, status: StatusCode) -> HttpResponse {
+        let format = preferred_format(req);
+        match format.encode(&self.0).context("Failed to serialize the response") {
+            Ok(body) => HttpResponse::build(status)
+                .content_type(format.mime_type())
+                .append_header((header::VARY, "Accept"))
+                .body(body),
+            Err(err) => utils::Error::from(err).error_response(),
+        }
+    }
+}
+
+impl<T: Serialize> Responder for Negotiated<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse {
+        self.into_response(req, StatusCode::OK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use dearrow_browser_api::formats::Format;
+
+    use super::preferred_format;
+
+    fn format_for(accept: Option<&str>) -> Format {
+        let mut req = TestRequest::default();
+        if let Some(accept) = accept {
+            req = req.insert_header(("Accept", accept));
+        }
+        preferred_format(&req.to_http_request())
+    }
+
+    #[test]
+    fn defaults_to_json() {
+        assert_eq!(format_for(None), Format::Json);
+        assert_eq!(format_for(Some("*/*")), Format::Json);
+        assert_eq!(format_for(Some("application/*")), Format::Json);
+        assert_eq!(format_for(Some("text/html")), Format::Json);
+        assert_eq!(format_for(Some("not a media type")), Format::Json);
+    }
+
+    #[test]
+    fn picks_listed_format() {
+        assert_eq!(format_for(Some("application/msgpack")), Format::MessagePack);
+        assert_eq!(format_for(Some("application/cbor")), Format::Cbor);
+        assert_eq!(format_for(Some("text/html, application/msgpack")), Format::MessagePack);
+        assert_eq!(format_for(Some("application/msgpack, application/json")), Format::MessagePack);
+    }
+
+    #[test]
+    fn respects_q_values() {
+        assert_eq!(format_for(Some("application/json;q=0.5, application/cbor")), Format::Cbor);
+        assert_eq!(format_for(Some("application/msgpack;q=0.8, application/cbor;q=0.9")), Format::Cbor);
+        assert_eq!(format_for(Some("*/*;q=0.1, application/msgpack")), Format::MessagePack);
+        assert_eq!(format_for(Some("application/msgpack;q=0.1, */*")), Format::Json);
+    }
+}
@@ -22,17 +22,20 @@ use actix_web::{get, http::StatusCode, web, Either, HttpResponse};
 use error_handling::{anyhow, bail, ErrContext, ErrorContext, ResContext};
 use dearrow_browser_api::sync::{InnertubeChannel, InnertubeVideo, self as api};
 use log::{debug, warn};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response};
 use tokio::{fs::File, io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter}, task::JoinSet};
 use tokio_stream::{wrappers::LinesStream, StreamExt};
 
 use crate::{constants::*, utils::{ReqwestResponseExt, TemporaryFile}};
+use crate::formats::Negotiated;
+use crate::metrics::METRICS;
 use crate::middleware::{ETagCache, ETagCacheControl};
-use crate::state::{self, AppConfig, DBLock, GetChannelOutput};
+use crate::ratelimit::ClientIp;
+use crate::state::{self, AppConfig, ConfigState, DBState, GetChannelOutput};
 use crate::utils::{self, ExtendResponder, ResponderExt};
 
-type JsonResult<T> = utils::Result<web::Json<T>>;
-type JsonResultOrFetchProgress<T> = utils::Result<Either<web::Json<T>, (ExtendResponder<web::Json<api::ChannelFetchProgress>>, StatusCode)>>;
+type ApiResult<T> = utils::Result<Negotiated<T>>;
+type ApiResultOrFetchProgress<T> = utils::Result<Either<Negotiated<T>, (ExtendResponder<Negotiated<api::ChannelFetchProgress>>, StatusCode)>>;
 
 pub fn configure_disabled(cfg: &mut web::ServiceConfig) {
     cfg.default_service(web::to(disabled_route));
@@ -48,10 +51,19 @@ async fn disabled_route() -> HttpResponse {
     HttpResponse::NotFound().body("Innertube endpoints are disabled on this DeArrow Browser instance.")
 }
 
+/// Sends a request to youtube, counting it in the metrics under `kind`
+async fn send_request(kind: &'static str, req: RequestBuilder) -> reqwest::Result<Response> {
+    let result = req.send().await;
+    METRICS.record_innertube_request(kind, result.as_ref().is_ok_and(|r| r.status().is_success()));
+    result
+}
+
 
 // https://github.com/ajayyy/DeArrow/blob/c4e1375380bc3b0cb202af283f0e7b4e5e6e30f1/src/thumbnails/thumbnailData.ts#L230
 #[get("/video/{video_id}")]
-async fn get_innertube_video(path: web::Path<String>, client: web::ThinData<Client>, config: web::Data<AppConfig>) -> JsonResult<InnertubeVideo> {
+async fn get_innertube_video(path: web::Path<String>, config: ConfigState) -> ApiResult<InnertubeVideo> {
+    let client = config.client();
+    let config = config.load();
     let vid = path.as_str();
     let url = IT_PLAYER_URL.clone();
     let input = {
@@ -70,34 +82,35 @@ async fn get_innertube_video(path: web::Path<String>, client: web::ThinData<Clie
     if let Some(ref visitor_data) = config.innertube.visitor_data {
         req = req.header("X-Goog-Visitor-Id", visitor_data);
     }
-    let resp = req.send().await.context("Failed to send innertube request")?;
+    let resp = send_request("player", req).await.context("Failed to send innertube request")?;
     let resp = resp.error_for_status().context("Innertube request failed")?;
     let result: it::player::out::Video = resp.json().await.context("Failed to deserialize innertube response")?;
     if result.video_details.video_id != vid {
         return Err(anyhow!("Innertube returned the wrong videoid - requested: {vid}, got: {}", result.video_details.video_id).into());
     }
-    Ok(web::Json(InnertubeVideo {
+    Ok(Negotiated(InnertubeVideo {
         video_id: vid.into(),
         duration: result.video_details.length_seconds,
     }))
 }
 
 #[get("/channel/{handle}", wrap="ETagCache")]
-async fn get_channel_endpoint(path: web::Path<String>, db_lock: DBLock) -> JsonResultOrFetchProgress<InnertubeChannel> {
+async fn get_channel_endpoint(client: ClientIp, path: web::Path<String>, db_state: DBState) -> ApiResultOrFetchProgress<InnertubeChannel> {
     let channel_cache = {
-        let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+        let db = db_state.load()?;
         db.channel_cache.clone()
     };
-    let channel_data = channel_cache.get_channel(path.into_inner().as_str()).await.context("Failed to get channel info")?;
+    let channel_data = channel_cache.get_channel(path.into_inner().as_str(), client).await.context("Failed to get channel info")?;
 
     match channel_data {
         GetChannelOutput::Pending(progress) => {
-            let mut resp = web::Json(api::ChannelFetchProgress::from(&progress)).extend();
+            let mut resp = Negotiated(api::ChannelFetchProgress::from(&progress)).extend();
             resp.extensions.insert(ETagCacheControl::DoNotCache);
             Ok(Either::Right((resp, *NOT_READY_YET)))
         },
+        GetChannelOutput::RateLimited(retry_after) => Err(utils::Error::RateLimited(retry_after)),
         GetChannelOutput::Resolved(result) => {
-            Ok(Either::Left(web::Json(InnertubeChannel {
+            Ok(Either::Left(Negotiated(InnertubeChannel {
                 channel_name: result.channel_name.deref().into(),
                 num_videos: result.num_videos as u64,
                 num_vods: result.num_vods as u64,
@@ -110,7 +123,7 @@ async fn get_channel_endpoint(path: web::Path<String>, db_lock: DBLock) -> JsonR
 }
 //
 // #[get("/channel_albums/{handle}")]
-// async fn get_playlist_endpoint(path: web::Path<String>, client: web::ThinData<Client>, config: web::Data<AppConfig>) -> JsonResult<Vec<Vec<String>>> {
+// async fn get_playlist_endpoint(path: web::Path<String>, client: web::ThinData<Client>, config: web::Data<AppConfig>) -> ApiResult<Vec<Vec<String>>> {
 //     let ucid = handle_to_ucid(&client, path.into_inner().as_str()).await?;
 //     Ok(web::Json(browse_releases_tab((*client).clone(), config.clone().into_inner(), ucid.into(), Arc::default()).await?))
 // }
@@ -137,7 +150,7 @@ pub async fn handle_to_ucid(client: &Client, handle: &str) -> Result<String, Err
         }
     };
 
-    let resp = client.get(url).send().await.context("Failed to send channel page request")?;
+    let resp = send_request("channel_page", client.get(url)).await.context("Failed to send channel page request")?;
     let resp = resp.error_for_status().context("Channel page request failed")?;
     let page = resp.text().await.context("Failed to receive the channel page")?;
 
@@ -213,7 +226,7 @@ pub async fn browse_channel(client: Client, config: Arc<AppConfig>, mode: &Brows
 
     'outer: while let Some(request) = pending_requests.pop_front() {
         let is_continuation = request.continuation.is_some();
-        let resp = client.post(IT_BROWSE_URL.clone()).json(&request).send().await.context("Failed to send browse request")?;
+        let resp = send_request("browse", client.post(IT_BROWSE_URL.clone()).json(&request)).await.context("Failed to send browse request")?;
         let resp = resp.error_for_status().context("Browse request failed")?;
 
         let results = if is_continuation {
@@ -362,7 +375,7 @@ pub async fn browse_playlist(client: Client, config: Arc<AppConfig>, plid: Strin
 
     while let Some(request) = pending_requests.pop_front() {
         let is_continuation = request.continuation.is_some();
-        let resp = client.post(IT_BROWSE_URL.clone()).json(&request).send().await.context("Failed to send browse request")?;
+        let resp = send_request("browse", client.post(IT_BROWSE_URL.clone()).json(&request)).await.context("Failed to send browse request")?;
         let resp = resp.error_for_status().context("Browse request failed")?;
 
         let results = if is_continuation {
@@ -467,7 +480,7 @@ pub async fn browse_releases_tab(client: Client, config: Arc<AppConfig>, ucid: A
 
     'outer: while let Some(request) = pending_requests.pop_front() {
         let is_continuation = request.continuation.is_some();
-        let resp = client.post(IT_BROWSE_URL.clone()).json(&request).send().await.context("Failed to send browse request")?;
+        let resp = send_request("browse", client.post(IT_BROWSE_URL.clone()).json(&request)).await.context("Failed to send browse request")?;
         let resp = resp.error_for_status().context("Browse request failed")?;
 
         let results = if is_continuation {
@@ -543,7 +556,7 @@ pub async fn browse_releases_homepage(client: Client, config: Arc<AppConfig>, uc
 
     'outer: while let Some(request) = pending_requests.pop_front() {
         let is_continuation = request.continuation.is_some();
-        let resp = client.post(IT_BROWSE_URL.clone()).json(&request).send().await.context("Failed to send browse request")?;
+        let resp = send_request("browse", client.post(IT_BROWSE_URL.clone()).json(&request)).await.context("Failed to send browse request")?;
         let resp = resp.error_for_status().context("Browse request failed")?;
 
         let results = if is_continuation {
@@ -588,12 +601,12 @@ pub async fn browse_releases_homepage(client: Client, config: Arc<AppConfig>, uc
             };
 
             // send another request!!!
-            let resp = client.post(IT_BROWSE_URL.clone()).json(&it::browse::Input {
+            let resp = send_request("browse", client.post(IT_BROWSE_URL.clone()).json(&it::browse::Input {
                 continuation: Some(continuation_endpoint.continuation_command.token),
                 context: it::Context::default(),
                 browse_id: None,
                 params: None,
-            }).send().await.context("Failed to send followup browse continuation request")?;
+            })).await.context("Failed to send followup browse continuation request")?;
             let resp = resp.error_for_status().context("Followup browse continuation request failed")?;
             let mut resp: it::browse::out::ShelfContinuation = resp.json_debug("releases_home").await.context("Failed to decode followup browse continuation response")?;
 
@@ -0,0 +1,54 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2023-2024 mini_bomba
+*  
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! The server of the browser, as a library
+//!
+//! [`DeArrowBrowser`] sets up the shared state of the server and configures its services,
+//! so that they can be mounted in other actix-web applications.
+//! [`server::serve`] runs the whole server, as done by the `dearrow-browser-server` binary.
+mod app;
+pub mod cli;
+mod constants;
+mod utils;
+mod routes;
+pub mod state;
+mod sbserver_emulation;
+mod middleware;
+mod innertube;
+mod feed;
+mod formats;
+mod atom;
+mod badges;
+mod openapi;
+mod opengraph;
+mod metrics;
+mod ratelimit;
+mod auth;
+mod watcher;
+mod webhooks;
+mod reload;
+mod sqlite_export;
+pub mod server;
+mod tls;
+mod systemd;
+
+pub use app::{DeArrowBrowser, DeArrowBrowserBuilder};
+
+mod built_info {
+    // Contents generated by buildscript, using built
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
@@ -0,0 +1,264 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::HashMap, fmt::{self, Display, Write}, sync::{atomic::{AtomicU64, Ordering::Relaxed}, LazyLock, Mutex, MutexGuard, PoisonError}, time::Duration};
+
+use actix_web::{http::{header, Method}, web, HttpRequest, HttpResponse};
+use dearrow_parser::ThumbnailFlags;
+use error_handling::{ErrorContext, ResContext};
+use futures::join;
+use log::debug;
+
+use crate::auth::authorize;
+use crate::constants::*;
+use crate::reload::ReloadJobs;
+use crate::state::{ConfigState, DBState, DatabaseState, TokenScope};
+use crate::utils;
+
+/// Global metrics registry, rendered in the Prometheus text format by [`get_metrics`]
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Default)]
+struct Histogram {
+    /// Non-cumulative counts for each bucket in [`METRICS_LATENCY_BUCKETS`], followed by the `+Inf` bucket
+    buckets: [u64; METRICS_LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = METRICS_LATENCY_BUCKETS.iter().position(|b| value <= *b).unwrap_or(METRICS_LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) -> fmt::Result {
+        let mut cumulative = 0;
+        for (bound, count) in METRICS_LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}")?;
+        }
+        writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count)?;
+        writeln!(out, "{name}_sum{{{labels}}} {}", self.sum)?;
+        writeln!(out, "{name}_count{{{labels}}} {}", self.count)
+    }
+}
+
+#[derive(Default)]
+struct RouteMetrics {
+    statuses: HashMap<u16, u64>,
+    latency: Histogram,
+}
+
+#[derive(Default)]
+struct InnertubeMetrics {
+    requests: u64,
+    errors: u64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Keyed by the request method and the matched route pattern
+    routes: Mutex<HashMap<(Method, String), RouteMetrics>>,
+    /// Keyed by the kind of innertube request
+    innertube: Mutex<HashMap<&'static str, InnertubeMetrics>>,
+    reloads_succeeded: AtomicU64,
+    reloads_failed: AtomicU64,
+    /// In microseconds
+    last_reload_duration: AtomicU64,
+    /// In microseconds
+    total_reload_duration: AtomicU64,
+    pub channel_cache_hits: AtomicU64,
+    pub channel_cache_misses: AtomicU64,
+    pub sbserver_titles_served: AtomicU64,
+    pub sbserver_thumbnails_served: AtomicU64,
+}
+
+/// Metrics are only counters, so a panic while holding the lock can't leave them in an invalid state
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: Method, route: String, status: u16, duration: Duration) {
+        let mut routes = lock(&self.routes);
+        let route = routes.entry((method, route)).or_default();
+        *route.statuses.entry(status).or_default() += 1;
+        route.latency.observe(duration.as_secs_f64());
+    }
+
+    pub fn record_reload(&self, duration: Duration, success: bool) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.last_reload_duration.store(micros, Relaxed);
+        self.total_reload_duration.fetch_add(micros, Relaxed);
+        if success {
+            self.reloads_succeeded.fetch_add(1, Relaxed);
+        } else {
+            self.reloads_failed.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub fn record_innertube_request(&self, kind: &'static str, success: bool) {
+        let mut innertube = lock(&self.innertube);
+        let entry = innertube.entry(kind).or_default();
+        entry.requests += 1;
+        if !success {
+            entry.errors += 1;
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl Display) -> fmt::Result {
+    write_header(out, name, kind, help)?;
+    writeln!(out, "{name} {value}")
+}
+
+fn micros_to_secs(micros: u64) -> f64 {
+    Duration::from_micros(micros).as_secs_f64()
+}
+
+struct ChannelCacheCounts {
+    cached: usize,
+    pending: usize,
+    fscached: usize,
+}
+
+async fn render_metrics(db_state: &DBState, jobs: &ReloadJobs) -> Result<String, ErrorContext> {
+    let db = db_state.try_load();
+    let channels = match db {
+        None => None,
+        Some(ref db) => {
+            let (cached, pending, fscached) = join!(
+                db.channel_cache.num_channels_cached(),
+                db.channel_cache.num_channels_pending(),
+                db.channel_cache.num_channels_fscached(),
+            );
+            Some(ChannelCacheCounts { cached, pending, fscached })
+        },
+    };
+    let mut out = String::new();
+    write_metrics(&mut out, db.as_deref(), jobs.is_running(), channels.as_ref()).context("Failed to write metrics")?;
+    Ok(out)
+}
+
+fn write_metrics(out: &mut String, db: Option<&DatabaseState>, updating: bool, channels: Option<&ChannelCacheCounts>) -> fmt::Result {
+    {
+        let routes = lock(&METRICS.routes);
+        write_header(out, "dearrow_browser_http_requests_total", "counter", "Number of HTTP requests handled, by route and status code")?;
+        for ((method, route), metrics) in routes.iter() {
+            for (status, count) in &metrics.statuses {
+                writeln!(out, "dearrow_browser_http_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {count}", escape_label(route))?;
+            }
+        }
+        write_header(out, "dearrow_browser_http_request_duration_seconds", "histogram", "Time spent handling HTTP requests, by route")?;
+        for ((method, route), metrics) in routes.iter() {
+            metrics.latency.write(out, "dearrow_browser_http_request_duration_seconds", &format!("method=\"{method}\",route=\"{}\"", escape_label(route)))?;
+        }
+    }
+
+    write_metric(out, "dearrow_browser_db_updating", "gauge", "Whether a database reload is currently in progress", u8::from(updating))?;
+    write_metric(out, "dearrow_browser_db_loaded", "gauge", "Whether the database is loaded, requests for data fail while it isn't", u8::from(db.is_some()))?;
+    if let Some(db) = db {
+        write_metric(out, "dearrow_browser_db_titles", "gauge", "Number of titles in the database", db.db.titles.len())?;
+        write_metric(out, "dearrow_browser_db_thumbnails", "gauge", "Number of thumbnails in the database", db.db.thumbnails.len())?;
+        write_metric(out, "dearrow_browser_db_usernames", "gauge", "Number of usernames in the database", db.db.usernames.len())?;
+        write_metric(out, "dearrow_browser_db_vip_users", "gauge", "Number of VIP users in the database", db.db.vip_users.len())?;
+        write_metric(out, "dearrow_browser_db_warnings", "gauge", "Number of warnings in the database", db.db.warnings.len())?;
+        write_metric(out, "dearrow_browser_db_video_infos", "gauge", "Number of video infos in the database", db.video_info_count)?;
+        write_metric(out, "dearrow_browser_db_uncut_segments", "gauge", "Number of uncut segments in the database", db.uncut_segment_count)?;
+        write_metric(out, "dearrow_browser_db_errors", "gauge", "Number of errors encountered while loading the database", db.errors.len())?;
+        write_metric(out, "dearrow_browser_db_strings", "gauge", "Number of strings in the string set", db.string_set.set.len())?;
+        write_metric(out, "dearrow_browser_db_last_updated_timestamp_seconds", "gauge", "Time of the last successful database load", db.last_updated / 1000)?;
+
+        write_header(out, "dearrow_browser_db_thumbnails_flagged", "gauge", "Number of thumbnails in the database with the given flag")?;
+        // counted once per snapshot, instead of scanning all thumbnails on every scrape
+        for &(flag, count) in &db.thumbnail_flag_counts {
+            let name = match flag {
+                ThumbnailFlags::Original => "original",
+                ThumbnailFlags::Locked => "locked",
+                ThumbnailFlags::ShadowHidden => "shadow_hidden",
+                ThumbnailFlags::Removed => "removed",
+                ThumbnailFlags::MissingVotes => "missing_votes",
+                ThumbnailFlags::MissingTimestamp => "missing_timestamp",
+            };
+            writeln!(out, "dearrow_browser_db_thumbnails_flagged{{flag=\"{name}\"}} {count}")?;
+        }
+    }
+
+    write_header(out, "dearrow_browser_reloads_total", "counter", "Number of database reloads, by result")?;
+    writeln!(out, "dearrow_browser_reloads_total{{result=\"success\"}} {}", METRICS.reloads_succeeded.load(Relaxed))?;
+    writeln!(out, "dearrow_browser_reloads_total{{result=\"failure\"}} {}", METRICS.reloads_failed.load(Relaxed))?;
+    write_metric(out, "dearrow_browser_last_reload_duration_seconds", "gauge", "Time spent on the last database reload", micros_to_secs(METRICS.last_reload_duration.load(Relaxed)))?;
+    write_metric(out, "dearrow_browser_reload_duration_seconds_total", "counter", "Total time spent on database reloads", micros_to_secs(METRICS.total_reload_duration.load(Relaxed)))?;
+
+    write_metric(out, "dearrow_browser_channel_cache_hits_total", "counter", "Number of channel lookups served from the channel cache", METRICS.channel_cache_hits.load(Relaxed))?;
+    write_metric(out, "dearrow_browser_channel_cache_misses_total", "counter", "Number of channel lookups that started a new fetch", METRICS.channel_cache_misses.load(Relaxed))?;
+    if let Some(channels) = channels {
+        write_metric(out, "dearrow_browser_channel_cache_channels", "gauge", "Number of channels in the in-memory channel cache", channels.cached)?;
+        write_metric(out, "dearrow_browser_channel_cache_pending_fetches", "gauge", "Number of channels currently being fetched", channels.pending)?;
+        write_metric(out, "dearrow_browser_channel_cache_fscached_channels", "gauge", "Number of channels cached on disk", channels.fscached)?;
+    }
+
+    {
+        let innertube = lock(&METRICS.innertube);
+        write_header(out, "dearrow_browser_innertube_requests_total", "counter", "Number of requests sent to innertube, by kind")?;
+        for (kind, metrics) in innertube.iter() {
+            writeln!(out, "dearrow_browser_innertube_requests_total{{kind=\"{kind}\"}} {}", metrics.requests)?;
+        }
+        write_header(out, "dearrow_browser_innertube_errors_total", "counter", "Number of failed requests sent to innertube, by kind")?;
+        for (kind, metrics) in innertube.iter() {
+            writeln!(out, "dearrow_browser_innertube_errors_total{{kind=\"{kind}\"}} {}", metrics.errors)?;
+        }
+    }
+
+    write_header(out, "dearrow_browser_sbserver_branding_served_total", "counter", "Number of submissions returned by the SponsorBlockServer emulation branding endpoints")?;
+    writeln!(out, "dearrow_browser_sbserver_branding_served_total{{kind=\"title\"}} {}", METRICS.sbserver_titles_served.load(Relaxed))?;
+    writeln!(out, "dearrow_browser_sbserver_branding_served_total{{kind=\"thumbnail\"}} {}", METRICS.sbserver_thumbnails_served.load(Relaxed))?;
+
+    Ok(())
+}
+
+fn metrics_response(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8"))
+        .body(body)
+}
+
+/// Serves the metrics without authentication, used on the dedicated metrics listener
+pub async fn get_metrics(db_state: DBState, jobs: web::Data<ReloadJobs>) -> utils::Result<HttpResponse> {
+    let body = render_metrics(&db_state, &jobs).await?;
+    Ok(metrics_response(body))
+}
+
+/// Serves the metrics on the main listener, requiring a token with the `metrics` scope
+pub async fn get_metrics_authenticated(req: HttpRequest, db_state: DBState, jobs: web::Data<ReloadJobs>, config: ConfigState) -> utils::Result<HttpResponse> {
+    let config = config.load();
+    let token = authorize(&req, &config, TokenScope::Metrics, None)?;
+    debug!("Metrics scraped using the '{token}' token");
+    get_metrics(db_state, jobs).await
+}
@@ -16,15 +16,20 @@
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::{future::{ready, Ready}, time::{Duration, Instant}};
+use std::{collections::HashMap, future::{ready, Ready}, sync::{Mutex, PoisonError}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
-use actix_web::{body::{BoxBody, EitherBody, MessageBody}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, error::HttpError, http::{header::{Accept, CacheControl, CacheDirective, ContentType, ETag, Header, IfNoneMatch}, StatusCode}, web, Error, HttpResponseBuilder};
+use actix_web::{body::{self, BoxBody, EitherBody, MessageBody}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, error::{ErrorInternalServerError, HttpError}, http::{header::{self, Accept, CacheControl, CacheDirective, ContentType, ETag, EntityTag, Header, IfModifiedSince, IfNoneMatch, LastModified}, StatusCode}, web, Error, HttpResponseBuilder};
+use base64::prelude::{BASE64_URL_SAFE_NO_PAD, Engine};
 use error_handling::SerializableError;
 use futures::{future::LocalBoxFuture, FutureExt};
 use log::{error, warn};
+use sha2::{Digest, Sha256};
 
+use crate::metrics::METRICS;
+use crate::ratelimit::{ClientIp, RateLimitGroup, RateLimiters};
 use crate::constants::*;
-use crate::state::{DBLock, AppConfig};
+use crate::formats::preferred_format;
+use crate::state::ConfigState;
 use crate::utils::{self, HeaderMapExt, SerializableErrorResponseMarker};
 
 
@@ -40,13 +45,48 @@ pub enum ETagCacheControl {
     DoNotCache,
 }
 
+/// Remembers when the content of each resource last changed, for the `Last-Modified` header
+#[derive(Default)]
+pub struct ResourceVersions {
+    versions: Mutex<HashMap<String, ResourceVersion>>,
+}
+
+struct ResourceVersion {
+    etag: String,
+    modified: SystemTime,
+}
+
+impl ResourceVersions {
+    /// Returns the time when the resource identified by `key` started having the given `ETag`
+    fn last_modified(&self, key: String, etag: &EntityTag) -> SystemTime {
+        let now = SystemTime::now();
+        let mut versions = self.versions.lock().unwrap_or_else(PoisonError::into_inner);
+        // forgetting versions is harmless, as they are recreated with the current time, which can't be earlier than the actual change
+        if versions.len() >= RESOURCE_VERSIONS_LIMIT && !versions.contains_key(&key) {
+            versions.clear();
+        }
+        let version = versions.entry(key).or_insert_with(|| ResourceVersion { etag: etag.tag().to_owned(), modified: now });
+        if version.etag != etag.tag() {
+            etag.tag().clone_into(&mut version.etag);
+            version.modified = now;
+        }
+        version.modified
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Tags successful responses with an `ETag` hashed from their body and a `Last-Modified` time,
+/// and replaces them with `304 Not Modified` if they match the conditional headers of the request
 pub struct ETagCache;
 
 impl<S, B> Transform<S, ServiceRequest> for ETagCache
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
     type Response = ServiceResponse<EitherBody<B, BoxBody>>;
     type Error = Error;
@@ -67,7 +107,7 @@ impl<S, B> Service<ServiceRequest> for ETagCacheInstance<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
-    B: 'static,
+    B: MessageBody + 'static,
 {
     type Response = ServiceResponse<EitherBody<B, BoxBody>>;
     type Error = Error;
@@ -76,24 +116,15 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let db = req.app_data::<DBLock>().unwrap().clone();
-        let Ok(etag) = db.read().map(|db| db.get_etag()) else {
-            return ready(Err(utils::Error::from(DB_READ_ERR.clone()).into())).boxed_local();
-        };
-
         let inm = match IfNoneMatch::parse(&req) {
             Ok(inm) => inm,
             Err(err) => return ready(Err(err.into())).boxed_local(),
         };
-
-        if let IfNoneMatch::Items(etags) = inm {
-            if etags.iter().any(|e| e.weak_eq(&etag)) {
-                let mut resp = HttpResponseBuilder::new(StatusCode::NOT_MODIFIED);
-                resp.append_header(ETag(etag))
-                    .append_header(CacheControl(vec![CacheDirective::NoCache]));
-                return ready(Ok(req.into_response(resp).map_into_right_body())).boxed_local();
-            }
-        }
+        // invalid dates are ignored, as required by RFC 9110
+        let ims = IfModifiedSince::parse(&req).ok();
+        let versions = req.app_data::<web::Data<ResourceVersions>>().cloned();
+        // the body differs between formats, so each of them is versioned separately
+        let key = format!("{} {}", preferred_format(req.request()).mime_type(), req.uri());
 
         let srv = self.service.call(req);
 
@@ -101,20 +132,45 @@ where
             let mut resp = srv.await?;
 
             let extension = resp.response().extensions().get::<ETagCacheControl>().copied();
-            match extension {
-                None => {
-                    let headers = resp.headers_mut();
-                    headers.append_header(ETag(etag)).map_err(Into::<actix_web::error::HttpError>::into)?;
-                    headers.append_header(CacheControl(vec![CacheDirective::NoCache])).map_err(Into::<actix_web::error::HttpError>::into)?;
-                },
-                Some(ETagCacheControl::DoNotCache) => {
-                    let headers = resp.headers_mut();
-                    headers.append_header(CacheControl(vec![CacheDirective::NoCache, CacheDirective::NoStore])).map_err(Into::<actix_web::error::HttpError>::into)?;
-                },
+            if let Some(ETagCacheControl::DoNotCache) = extension {
+                let headers = resp.headers_mut();
+                headers.append_header(CacheControl(vec![CacheDirective::NoCache, CacheDirective::NoStore])).map_err(Into::<actix_web::error::HttpError>::into)?;
+                return Ok(resp.map_into_left_body());
+            }
+            if resp.status() != StatusCode::OK {
+                return Ok(resp.map_into_left_body());
             }
 
+            let (req, resp) = resp.into_parts();
+            let (resp, body) = resp.into_parts();
+            let body = body::to_bytes(body).await.map_err(|err| ErrorInternalServerError(err.into()))?;
+            let digest = Sha256::digest(&body);
+            let etag = EntityTag::new_weak(BASE64_URL_SAFE_NO_PAD.encode(&digest[..16]));
+            let last_modified = versions.map(|v| v.last_modified(key, &etag));
+
+            let not_modified = match inm {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(ref etags) if !etags.is_empty() => etags.iter().any(|e| e.weak_eq(&etag)),
+                // If-Modified-Since is only considered without If-None-Match, with the one second precision of HTTP dates
+                IfNoneMatch::Items(..) => ims.zip(last_modified).is_some_and(|(IfModifiedSince(since), modified)| unix_secs(modified) <= unix_secs(since.into())),
+            };
+            let mut resp = if not_modified {
+                let mut not_modified = HttpResponseBuilder::new(StatusCode::NOT_MODIFIED);
+                if let Some(vary) = resp.headers().get(header::VARY) {
+                    not_modified.append_header((header::VARY, vary.clone()));
+                }
+                not_modified.finish()
+            } else {
+                resp.set_body(BoxBody::new(body))
+            };
+            let headers = resp.headers_mut();
+            headers.append_header(ETag(etag)).map_err(Into::<actix_web::error::HttpError>::into)?;
+            if let Some(modified) = last_modified {
+                headers.append_header(LastModified(modified.into())).map_err(Into::<actix_web::error::HttpError>::into)?;
+            }
+            headers.append_header(CacheControl(vec![CacheDirective::NoCache])).map_err(Into::<actix_web::error::HttpError>::into)?;
 
-            Ok(resp.map_into_left_body())
+            Ok(ServiceResponse::new(req, resp).map_into_right_body())
         }.boxed_local()
     }
 }
@@ -159,8 +215,8 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let config = req.app_data::<web::Data<AppConfig>>().unwrap();
-        if !config.enable_timings_header {
+        let config = req.app_data::<ConfigState>().unwrap();
+        if !config.load().enable_timings_header {
             return self.service.call(req).boxed_local()
         }
         let start = Instant::now();
@@ -327,3 +383,109 @@ where
         }.boxed_local()
     }
 }
+
+
+// Request metrics middleware
+
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestMetricsInstance<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsInstance { service }))
+    }
+}
+
+pub struct RequestMetricsInstance<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsInstance<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let srv = self.service.call(req);
+
+        async move {
+            let resp = srv.await?;
+            let request = resp.request();
+            // static files and unknown paths share a single label, instead of creating one for every path
+            let route = request.match_pattern().filter(|p| !p.is_empty()).unwrap_or_else(|| "<unmatched>".to_owned());
+            METRICS.record_request(request.method().clone(), route, resp.status().as_u16(), start.elapsed());
+            Ok(resp)
+        }.boxed_local()
+    }
+}
+
+
+// Rate limiting middleware
+
+/// Limits the rate of requests each client can make to the wrapped services, using the limiter of the given group
+pub struct RateLimited(pub RateLimitGroup);
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimited
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitedInstance<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitedInstance { service, group: self.0 }))
+    }
+}
+
+pub struct RateLimitedInstance<S> {
+    service: S,
+    group: RateLimitGroup,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitedInstance<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let config = req.app_data::<ConfigState>().unwrap().load();
+        let limiters = req.app_data::<web::Data<RateLimiters>>().unwrap();
+        if let ClientIp(Some(ip)) = ClientIp::from_request(req.request(), &config) {
+            if let Err(retry_after) = limiters.group(self.group).check(ip) {
+                let resp = req.error_response(utils::Error::RateLimited(retry_after));
+                return ready(Ok(resp.map_into_right_body())).boxed_local();
+            }
+        }
+        self.service.call(req).map(|res| res.map(ServiceResponse::map_into_left_body)).boxed_local()
+    }
+}
@@ -0,0 +1,786 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Hand-written `OpenAPI` 3.1 description of the server's routes
+//!
+//! Schemas of the `dearrow-browser-api` structs are declared with the [`object_schema`] macro,
+//! which fails to compile if the listed fields stop matching the struct definition.
+use std::sync::LazyLock;
+
+use dearrow_browser_api::sync::*;
+use error_handling::SerializableError;
+use serde_json::{json, Map, Value};
+
+use crate::constants::*;
+
+pub static OPENAPI_DOCUMENT: LazyLock<Value> = LazyLock::new(document);
+
+/// Builds the schema of a struct, with every field required
+///
+/// The listed fields are checked against the struct definition using an exhaustive pattern.
+macro_rules! object_schema {
+    ($ty:ident { $($field:ident: $schema:expr),* $(,)? }) => {{
+        #[allow(dead_code)]
+        fn check_fields(value: $ty) {
+            let $ty { $($field: _),* } = value;
+        }
+        let mut properties = Map::new();
+        $(properties.insert(stringify!($field).to_owned(), $schema);)*
+        json!({
+            "type": "object",
+            "required": [$(stringify!($field)),*],
+            "properties": properties,
+        })
+    }};
+}
+
+fn string() -> Value {
+    json!({"type": "string"})
+}
+
+fn boolean() -> Value {
+    json!({"type": "boolean"})
+}
+
+fn integer(format: &str) -> Value {
+    json!({"type": "integer", "format": format})
+}
+
+fn unsigned() -> Value {
+    json!({"type": "integer", "minimum": 0})
+}
+
+fn number() -> Value {
+    json!({"type": "number", "format": "double"})
+}
+
+fn timestamp() -> Value {
+    json!({"type": "integer", "format": "int64", "description": "Unix timestamp in milliseconds"})
+}
+
+fn array(items: Value) -> Value {
+    let mut schema = json!({"type": "array"});
+    schema["items"] = items;
+    schema
+}
+
+fn reference(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{name}")})
+}
+
+fn nullable(mut schema: Value) -> Value {
+    match schema.get("type").and_then(Value::as_str).map(str::to_owned) {
+        Some(ty) => {
+            schema["type"] = json!([ty, "null"]);
+            schema
+        },
+        None => Value::Object(Map::from_iter([("oneOf".to_owned(), Value::Array(vec![schema, json!({"type": "null"})]))])),
+    }
+}
+
+fn json_content(schema: Value) -> Value {
+    let mut content = json!({"application/json": {}});
+    content["application/json"]["schema"] = schema;
+    content
+}
+
+/// Content of responses that can also be encoded as `MessagePack` or CBOR, depending on the `Accept` header
+fn negotiated_content(schema: Value) -> Value {
+    let mut content = json!({"application/json": {}, "application/msgpack": {}, "application/cbor": {}});
+    content["application/json"]["schema"] = schema.clone();
+    content["application/msgpack"]["schema"] = schema.clone();
+    content["application/cbor"]["schema"] = schema;
+    content
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({"name": name, "in": "path", "required": true, "description": description, "schema": string()})
+}
+
+fn query_param(name: &str, required: bool, description: &str, schema: Value) -> Value {
+    let mut param = json!({"name": name, "in": "query", "required": required, "description": description});
+    param["schema"] = schema;
+    param
+}
+
+fn error_ref(name: &str) -> Value {
+    json!({"$ref": format!("#/components/responses/{name}")})
+}
+
+/// A JSON endpoint of the main API
+fn operation(tag: &str, summary: &str, parameters: Vec<Value>, response: Value, errors: &[(&str, &str)], cached: bool) -> Value {
+    let mut responses = Map::new();
+    responses.insert("200".to_owned(), json!({"description": "Success", "content": negotiated_content(response)}));
+    if cached {
+        responses.insert("304".to_owned(), error_ref("NotModified"));
+    }
+    for (status, name) in errors {
+        responses.insert((*status).to_owned(), error_ref(name));
+    }
+    responses.insert("500".to_owned(), error_ref("Error"));
+    responses.insert("503".to_owned(), error_ref("Unavailable"));
+    let mut op = json!({
+        "tags": [tag],
+        "summary": summary,
+    });
+    op["parameters"] = Value::Array(parameters);
+    op["responses"] = Value::Object(responses);
+    op
+}
+
+/// Same as [`operation`], but the endpoint may respond with 333 Not Ready Yet while channel data is fetched
+fn channel_operation(tag: &str, summary: &str, response: Value) -> Value {
+    let mut op = operation(tag, summary, vec![path_param("channel", "Channel handle or UCID")], response, &[("333", "NotReadyYet"), ("404", "InnertubeDisabled")], true);
+    op["description"] = json!("Requires innertube to be enabled on the instance.");
+    op
+}
+
+fn atom_operation(summary: &str, parameters: Vec<Value>, errors: &[(&str, &str)]) -> Value {
+    let mut op = operation("Atom feeds", summary, parameters, Value::Null, errors, true);
+    op["responses"]["200"]["content"] = json!({"application/atom+xml": {"schema": string()}});
+    op
+}
+
+fn badge_operation(summary: &str, parameters: Vec<Value>) -> Value {
+    let mut op = operation("Badges", summary, parameters, Value::Null, &[], true);
+    op["responses"]["200"]["content"] = json!({"image/svg+xml": {"schema": string()}});
+    op
+}
+
+fn schemas() -> Map<String, Value> {
+    let mut schemas = Map::new();
+    let mut add = |name: &str, schema: Value| schemas.insert(name.to_owned(), schema);
+
+    add("SerializableError", {
+        let _ = |e: SerializableError| { let SerializableError { context: _, cause: _ } = e; };
+        json!({
+            "type": "object",
+            "description": "An error, along with the chain of errors that caused it",
+            "required": ["context", "cause"],
+            "properties": {
+                "context": string(),
+                "cause": nullable(reference("SerializableError")),
+            },
+        })
+    });
+    add("StatusResponse", object_schema!(StatusResponse {
+        last_updated: timestamp(),
+        last_modified: timestamp(),
+        updating_now: boolean(),
+        titles: unsigned(),
+        thumbnails: unsigned(),
+        vip_users: unsigned(),
+        usernames: unsigned(),
+        errors: unsigned(),
+        string_count: nullable(unsigned()),
+        video_infos: unsigned(),
+        uncut_segments: unsigned(),
+        cached_channels: unsigned(),
+        fscached_channels: unsigned(),
+        server_version: string(),
+        server_git_hash: nullable(string()),
+        server_git_dirty: nullable(boolean()),
+        server_build_timestamp: nullable(integer("int64")),
+        server_startup_timestamp: integer("int64"),
+        reload: nullable(reference("ReloadJobStatus")),
+    }));
+    add("ReloadPhase", {
+        let _ = |p: ReloadPhase| match p {
+            ReloadPhase::Thumbnails | ReloadPhase::Titles | ReloadPhase::Usernames | ReloadPhase::VipUsers
+            | ReloadPhase::SponsorTimes | ReloadPhase::Warnings | ReloadPhase::Indexing | ReloadPhase::Swap => (),
+        };
+        json!({"type": "string", "enum": ["Thumbnails", "Titles", "Usernames", "VipUsers", "SponsorTimes", "Warnings", "Indexing", "Swap"]})
+    });
+    add("ReloadPhaseProgress", object_schema!(ReloadPhaseProgress {
+        phase: reference("ReloadPhase"),
+        rows: unsigned(),
+        elapsed_ms: unsigned(),
+        finished: boolean(),
+    }));
+    add("ReloadOutcome", {
+        let _ = |o: ReloadOutcome| match o { ReloadOutcome::Running | ReloadOutcome::Succeeded | ReloadOutcome::Failed => () };
+        json!({"type": "string", "enum": ["Running", "Succeeded", "Failed"]})
+    });
+    add("ReloadJobStatus", object_schema!(ReloadJobStatus {
+        id: unsigned(),
+        requested_by: string(),
+        started: timestamp(),
+        elapsed_ms: unsigned(),
+        phases: array(reference("ReloadPhaseProgress")),
+        outcome: reference("ReloadOutcome"),
+        error: nullable(string()),
+    }));
+    add("ReadinessResponse", object_schema!(ReadinessResponse {
+        ready: boolean(),
+        reload: nullable(reference("ReloadJobStatus")),
+    }));
+    add("ApiTitle", object_schema!(ApiTitle {
+        uuid: string(),
+        video_id: string(),
+        title: string(),
+        user_id: string(),
+        time_submitted: timestamp(),
+        votes: integer("int8"),
+        downvotes: integer("int8"),
+        original: boolean(),
+        locked: boolean(),
+        shadow_hidden: boolean(),
+        unverified: boolean(),
+        removed: boolean(),
+        votes_missing: boolean(),
+        score: integer("int8"),
+        username: nullable(string()),
+        vip: boolean(),
+    }));
+    add("ApiThumbnail", object_schema!(ApiThumbnail {
+        uuid: string(),
+        video_id: string(),
+        user_id: string(),
+        time_submitted: timestamp(),
+        timestamp: nullable(number()),
+        votes: integer("int8"),
+        downvotes: integer("int8"),
+        original: boolean(),
+        locked: boolean(),
+        shadow_hidden: boolean(),
+        removed: boolean(),
+        votes_missing: boolean(),
+        timestamp_missing: boolean(),
+        score: integer("int8"),
+        username: nullable(string()),
+        vip: boolean(),
+    }));
+    add("User", object_schema!(User {
+        user_id: string(),
+        username: nullable(string()),
+        username_locked: boolean(),
+        vip: boolean(),
+        title_count: unsigned(),
+        thumbnail_count: unsigned(),
+        warning_count: unsigned(),
+        active_warning_count: unsigned(),
+    }));
+    add("UserSearchResult", object_schema!(UserSearchResult {
+        users: array(reference("User")),
+        username_collisions: array(string()),
+        truncated: boolean(),
+    }));
+    add("Video", object_schema!(Video {
+        video_id: string(),
+        random_thumbnail: number(),
+        duration: nullable(number()),
+        fraction_unmarked: number(),
+        has_outro: boolean(),
+    }));
+    add("InnertubeVideo", object_schema!(InnertubeVideo {
+        video_id: string(),
+        duration: unsigned(),
+    }));
+    add("InnertubeChannel", object_schema!(InnertubeChannel {
+        channel_name: string(),
+        num_videos: unsigned(),
+        num_vods: unsigned(),
+        num_shorts: unsigned(),
+        num_releases: unsigned(),
+        total_videos: unsigned(),
+    }));
+    add("ChannelFetchProgress", object_schema!(ChannelFetchProgress {
+        videos: reference("BrowseProgress"),
+        vods: reference("BrowseProgress"),
+        shorts: reference("BrowseProgress"),
+        releases_tab: reference("BrowseProgress"),
+        releases_home: reference("BrowseProgress"),
+    }));
+    add("BrowseProgress", object_schema!(BrowseProgress {
+        videos_fetched: unsigned(),
+        videos_in_fscache: unsigned(),
+    }));
+    add("Extension", {
+        let _ = |e: Extension| match e { Extension::SponsorBlock | Extension::DeArrow => () };
+        json!({"type": "string", "enum": ["SponsorBlock", "DeArrow"]})
+    });
+    add("ApiWarning", object_schema!(ApiWarning {
+        warned_user_id: string(),
+        issuer_user_id: string(),
+        time_issued: timestamp(),
+        extension: reference("Extension"),
+        message: string(),
+        active: boolean(),
+    }));
+    add("LeaderboardEntry", object_schema!(LeaderboardEntry {
+        user_id: string(),
+        username: nullable(string()),
+        vip: boolean(),
+        title_count: unsigned(),
+        thumbnail_count: unsigned(),
+        locked_count: unsigned(),
+        average_score: number(),
+        recent_count: unsigned(),
+    }));
+    add("Leaderboards", object_schema!(Leaderboards {
+        titles: array(reference("LeaderboardEntry")),
+        thumbnails: array(reference("LeaderboardEntry")),
+        locked: array(reference("LeaderboardEntry")),
+        average_score: array(reference("LeaderboardEntry")),
+        recently_active: array(reference("LeaderboardEntry")),
+    }));
+    add("BrandingHiddenReason", {
+        let _ = |r: BrandingHiddenReason| match r {
+            BrandingHiddenReason::BelowVoteThreshold | BrandingHiddenReason::Removed | BrandingHiddenReason::ShadowHidden
+            | BrandingHiddenReason::MissingVotes | BrandingHiddenReason::MissingTimestamp
+            | BrandingHiddenReason::UnverifiedNotLocked | BrandingHiddenReason::Outranked => (),
+        };
+        json!({
+            "type": "string",
+            "enum": ["BelowVoteThreshold", "Removed", "ShadowHidden", "MissingVotes", "MissingTimestamp", "UnverifiedNotLocked", "Outranked"],
+        })
+    });
+    add("BrandingTitle", object_schema!(BrandingTitle {
+        title: reference("ApiTitle"),
+        hidden_reason: nullable(reference("BrandingHiddenReason")),
+    }));
+    add("BrandingThumbnail", object_schema!(BrandingThumbnail {
+        thumbnail: reference("ApiThumbnail"),
+        hidden_reason: nullable(reference("BrandingHiddenReason")),
+    }));
+    add("VideoBranding", object_schema!(VideoBranding {
+        video_id: string(),
+        titles: array(reference("BrandingTitle")),
+        thumbnails: array(reference("BrandingThumbnail")),
+        random_time: number(),
+        video_duration: nullable(number()),
+    }));
+    add("ReloadStartedEvent", object_schema!(ReloadStartedEvent {
+        timestamp: timestamp(),
+    }));
+    add("ReloadFinishedEvent", object_schema!(ReloadFinishedEvent {
+        last_updated: timestamp(),
+        new_titles: unsigned(),
+        new_thumbnails: unsigned(),
+    }));
+    add("ReloadFailedEvent", object_schema!(ReloadFailedEvent {
+        error: string(),
+    }));
+    add("FeedLaggedEvent", object_schema!(FeedLaggedEvent {
+        skipped: unsigned(),
+    }));
+    add("SubmissionFlag", {
+        let _ = |f: SubmissionFlag| match f {
+            SubmissionFlag::Original | SubmissionFlag::Locked | SubmissionFlag::ShadowHidden | SubmissionFlag::Unverified | SubmissionFlag::Removed => (),
+        };
+        json!({"type": "string", "enum": ["original", "locked", "shadow_hidden", "unverified", "removed"]})
+    });
+    add("WebhookEvent", {
+        let _ = |e: WebhookEvent| match e {
+            WebhookEvent::NewTitle { title: _ } | WebhookEvent::NewThumbnail { thumbnail: _ }
+            | WebhookEvent::TitleUpdated { title: _, added_flags: _, removed_flags: _ }
+            | WebhookEvent::ThumbnailUpdated { thumbnail: _, added_flags: _, removed_flags: _ } => (),
+        };
+        let variant = |name: &str, field: &str, schema: &str, updated: bool| {
+            let mut required = vec!["type", field];
+            let mut properties = Map::new();
+            properties.insert("type".to_owned(), json!({"const": name}));
+            properties.insert(field.to_owned(), reference(schema));
+            if updated {
+                required.extend(["added_flags", "removed_flags"]);
+                properties.insert("added_flags".to_owned(), array(reference("SubmissionFlag")));
+                properties.insert("removed_flags".to_owned(), array(reference("SubmissionFlag")));
+            }
+            json!({"type": "object", "required": required, "properties": properties})
+        };
+        json!({
+            "oneOf": [
+                variant("new_title", "title", "ApiTitle", false),
+                variant("new_thumbnail", "thumbnail", "ApiThumbnail", false),
+                variant("title_updated", "title", "ApiTitle", true),
+                variant("thumbnail_updated", "thumbnail", "ApiThumbnail", true),
+            ],
+            "discriminator": {"propertyName": "type"},
+        })
+    });
+    add("WebhookPayload", object_schema!(WebhookPayload {
+        webhook: string(),
+        last_updated: timestamp(),
+        events: array(reference("WebhookEvent")),
+    }));
+
+    // SponsorBlockServer emulation, these mirror the upstream API
+    add("SBApiTitle", json!({
+        "type": "object",
+        "required": ["title", "original", "votes", "locked", "UUID"],
+        "properties": {
+            "title": string(),
+            "original": boolean(),
+            "votes": integer("int8"),
+            "locked": boolean(),
+            "UUID": string(),
+            "userID": {"type": "string", "description": "Only present if `returnUserID` was set"},
+        },
+    }));
+    add("SBApiThumbnail", json!({
+        "type": "object",
+        "required": ["timestamp", "original", "votes", "locked", "UUID"],
+        "properties": {
+            "timestamp": nullable(number()),
+            "original": boolean(),
+            "votes": integer("int8"),
+            "locked": boolean(),
+            "UUID": string(),
+            "userID": {"type": "string", "description": "Only present if `returnUserID` was set"},
+        },
+    }));
+    add("SBApiVideo", json!({
+        "type": "object",
+        "required": ["titles", "thumbnails", "randomTime", "videoDuration"],
+        "properties": {
+            "titles": array(reference("SBApiTitle")),
+            "thumbnails": array(reference("SBApiThumbnail")),
+            "randomTime": number(),
+            "videoDuration": nullable(number()),
+        },
+    }));
+    add("SBUserInfo", json!({
+        "type": "object",
+        "required": ["userID", "userName", "titleSubmissionCount", "thumbnailSubmissionCount", "vip", "warnings", "warningReason", "deArrowWarningReason"],
+        "properties": {
+            "userID": string(),
+            "userName": string(),
+            "titleSubmissionCount": unsigned(),
+            "thumbnailSubmissionCount": unsigned(),
+            "vip": boolean(),
+            "warnings": unsigned(),
+            "warningReason": nullable(string()),
+            "deArrowWarningReason": nullable(string()),
+        },
+    }));
+    schemas
+}
+
+fn responses() -> Value {
+    let error_content = json!({
+        "text/plain": {"schema": string()},
+        "application/json": {"schema": reference("SerializableError")},
+    });
+    json!({
+        "Error": {
+            "description": "Error. Sent as plain text, unless `application/json` was explicitly listed in the `Accept` header.",
+            "content": error_content,
+        },
+        "BadRequest": {
+            "description": "Invalid request parameters",
+            "content": error_content,
+        },
+        "NotFound": {
+            "description": "Not found",
+            "content": error_content,
+        },
+        "InnertubeDisabled": {
+            "description": "Innertube requests are disabled on this instance",
+            "content": {"text/plain": {"schema": string()}},
+        },
+        "NotModified": {
+            "description": "The response has not changed since the one with the `ETag` from `If-None-Match` was sent, or since the `If-Modified-Since` date",
+        },
+        "NotReadyYet": {
+            "description": "333 Not Ready Yet - the server is still fetching the channel's videos. Request the same URL again later.",
+            "content": negotiated_content(reference("ChannelFetchProgress")),
+        },
+        "TooManyRequests": {
+            "description": "The client exceeded a rate limit configured on this instance",
+            "headers": {
+                "Retry-After": {
+                    "description": "Number of seconds to wait before retrying",
+                    "schema": unsigned(),
+                },
+            },
+            "content": {"text/plain": {"schema": string()}},
+        },
+        "Unavailable": {
+            "description": "The database is not loaded yet, or is being reloaded in low-memory mode",
+            "headers": {
+                "Retry-After": {
+                    "description": "Number of seconds to wait before retrying",
+                    "schema": unsigned(),
+                },
+            },
+            "content": {"text/plain": {"schema": string()}},
+        },
+    })
+}
+
+fn api_paths(paths: &mut Map<String, Value>) {
+    let pagination = || vec![
+        query_param("offset", false, "Number of newest entries to skip", json!({"type": "integer", "minimum": 0, "default": 0})),
+        query_param("count", false, "Number of entries to return", json!({"type": "integer", "minimum": 0, "maximum": 1024, "default": 50})),
+    ];
+    let mut add = |path: &str, method: &str, op: Value| {
+        paths.entry(path.to_owned()).or_insert_with(|| json!({}))[method] = op;
+    };
+
+    add("/api/", "get", json!({
+        "tags": ["Status"],
+        "summary": "Health check",
+        "responses": {"200": {"description": "Always returns `hi`", "content": {"text/plain": {"schema": string()}}}},
+    }));
+    add("/api/health", "get", json!({
+        "tags": ["Status"],
+        "summary": "Liveness check",
+        "responses": {"200": {"description": "Always returns `ok`, even before the database is loaded", "content": {"text/plain": {"schema": string()}}}},
+    }));
+    add("/api/ready", "get", json!({
+        "tags": ["Status"],
+        "summary": "Readiness check",
+        "responses": {
+            "200": {"description": "The database is loaded", "content": negotiated_content(reference("ReadinessResponse"))},
+            "503": {
+                "description": "The database is not loaded yet",
+                "headers": {"Retry-After": {"description": "Number of seconds to wait before retrying", "schema": unsigned()}},
+                "content": negotiated_content(reference("ReadinessResponse")),
+            },
+        },
+    }));
+    add("/api/status", "get", operation("Status", "Database and server status", vec![], reference("StatusResponse"), &[], false));
+    add("/api/errors", "get", operation("Status", "Errors encountered while loading the database", vec![], array(reference("SerializableError")), &[], false));
+    add("/api/reload", "post", json!({
+        "tags": ["Status"],
+        "summary": "Reload the database from the mirror",
+        "description": "Requires a token with the `reload` scope.",
+        "security": [{"token": []}],
+        "parameters": [
+            query_param("auth", false, "Deprecated, the auth secret from the server config. Use the Authorization header instead.", string()),
+            query_param("wait", false, "Respond only after the reload finishes", json!({"type": "boolean", "default": false})),
+        ],
+        "responses": {
+            "200": {"description": "Reload finished, only returned if `wait` is set", "content": negotiated_content(reference("ReloadJobStatus"))},
+            "202": {"description": "Reload started, its progress is reported in the `reload` field of `/api/status`", "content": negotiated_content(reference("ReloadJobStatus"))},
+            "403": {"description": "Invalid token, or the token lacks the `reload` scope"},
+            "404": {"description": "No token was provided"},
+            "409": {"description": "A reload is already in progress", "content": json_content(reference("SerializableError"))},
+            "500": {"description": "Reload failed, only returned if `wait` is set", "content": negotiated_content(reference("ReloadJobStatus"))},
+        },
+    }));
+    add("/api/config/reload", "post", json!({
+        "tags": ["Status"],
+        "summary": "Re-read the config file, applying the settings that can change at runtime",
+        "description": "Requires a token with the `reload` scope. The same happens when the server receives SIGHUP.",
+        "security": [{"token": []}],
+        "responses": {
+            "200": {"description": "Config reloaded, followed by a line for every changed setting that requires a restart", "content": {"text/plain": {"schema": string()}}},
+            "403": {"description": "Invalid token, or the token lacks the `reload` scope"},
+            "404": {"description": "No token was provided"},
+            "500": {"description": "The new config is invalid, the old one stays in use", "content": json_content(reference("SerializableError"))},
+        },
+    }));
+    add("/api/channel_cache/reset", "post", json!({
+        "tags": ["Status"],
+        "summary": "Drop all channels from the in-memory channel cache",
+        "description": "Requires a token with the `cache_admin` scope.",
+        "security": [{"token": []}],
+        "responses": {
+            "200": {"description": "Cache reset", "content": {"text/plain": {"schema": string()}}},
+            "403": {"description": "Invalid token, or the token lacks the `cache_admin` scope"},
+            "404": {"description": "No token was provided"},
+        },
+    }));
+    add("/api/feed", "get", json!({
+        "tags": ["Status"],
+        "summary": "Server-sent events stream of reloads and new submissions",
+        "description": "Event names: `reload_started` (ReloadStartedEvent), `reload_finished` (ReloadFinishedEvent), `reload_failed` (ReloadFailedEvent), `title` (ApiTitle), `thumbnail` (ApiThumbnail) and `lagged` (FeedLaggedEvent). Each event's data is JSON.",
+        "parameters": [
+            query_param("video_id", false, "Only send submissions for this video", string()),
+            query_param("user_id", false, "Only send submissions by this user", string()),
+            query_param("channel", false, "Only send submissions for videos of this channel (handle or UCID), requires innertube", string()),
+            query_param("submissions", false, "Set to false to only receive reload events", json!({"type": "boolean", "default": true})),
+        ],
+        "responses": {
+            "200": {"description": "Event stream", "content": {"text/event-stream": {"schema": string()}}},
+            "333": error_ref("NotReadyYet"),
+            "404": error_ref("NotFound"),
+            "500": error_ref("Error"),
+        },
+    }));
+    add("/api/openapi.json", "get", json!({
+        "tags": ["Status"],
+        "summary": "This document",
+        "responses": {"200": {"description": "OpenAPI document", "content": json_content(json!({"type": "object"}))}},
+    }));
+
+    add("/api/titles", "get", operation("Titles", "Newest titles", pagination(), array(reference("ApiTitle")), &[("400", "BadRequest")], true));
+    add("/api/titles/unverified", "get", operation("Titles", "Unverified titles", vec![], array(reference("ApiTitle")), &[], true));
+    add("/api/titles/broken", "get", operation("Titles", "Titles with missing votes", vec![], array(reference("ApiTitle")), &[], true));
+    add("/api/titles/uuid/{uuid}", "get", operation("Titles", "Title by UUID", vec![path_param("uuid", "Title UUID")], reference("ApiTitle"), &[("404", "NotFound")], true));
+    add("/api/titles/video_id/{video_id}", "get", operation("Titles", "Titles for a video", vec![path_param("video_id", "YouTube video ID")], array(reference("ApiTitle")), &[], true));
+    add("/api/titles/user_id/{user_id}", "get", operation("Titles", "Titles submitted by a user", vec![path_param("user_id", "Public user ID")], array(reference("ApiTitle")), &[], true));
+    add("/api/titles/channel/{channel}", "get", channel_operation("Titles", "Titles for videos of a channel", array(reference("ApiTitle"))));
+
+    add("/api/thumbnails", "get", operation("Thumbnails", "Newest thumbnails", pagination(), array(reference("ApiThumbnail")), &[("400", "BadRequest")], true));
+    add("/api/thumbnails/broken", "get", operation("Thumbnails", "Thumbnails with missing votes or timestamps", vec![], array(reference("ApiThumbnail")), &[], true));
+    add("/api/thumbnails/uuid/{uuid}", "get", operation("Thumbnails", "Thumbnail by UUID", vec![path_param("uuid", "Thumbnail UUID")], reference("ApiThumbnail"), &[("404", "NotFound")], true));
+    add("/api/thumbnails/video_id/{video_id}", "get", operation("Thumbnails", "Thumbnails for a video", vec![path_param("video_id", "YouTube video ID")], array(reference("ApiThumbnail")), &[], true));
+    add("/api/thumbnails/user_id/{user_id}", "get", operation("Thumbnails", "Thumbnails submitted by a user", vec![path_param("user_id", "Public user ID")], array(reference("ApiThumbnail")), &[], true));
+    add("/api/thumbnails/channel/{channel}", "get", channel_operation("Thumbnails", "Thumbnails for videos of a channel", array(reference("ApiThumbnail"))));
+
+    add("/api/users/user_id/{user_id}", "get", operation("Users", "User summary", vec![path_param("user_id", "Public user ID")], reference("User"), &[], true));
+    add("/api/users/search", "get", operation("Users", "Search users by username or user ID prefix", vec![
+        query_param("query", true, &format!("Search query, at least {USER_SEARCH_MIN_LENGTH} characters long"), string()),
+        query_param("count", false, "Maximum number of users to return", json!({"type": "integer", "minimum": 0, "maximum": USER_SEARCH_MAX_COUNT, "default": 20})),
+    ], reference("UserSearchResult"), &[("400", "BadRequest")], true));
+    add("/api/warnings/user_id/{user_id}/received", "get", operation("Users", "Warnings received by a user", vec![path_param("user_id", "Public user ID")], array(reference("ApiWarning")), &[], false));
+    add("/api/warnings/user_id/{user_id}/issued", "get", operation("Users", "Warnings issued by a user", vec![path_param("user_id", "Public user ID")], array(reference("ApiWarning")), &[], false));
+    add("/api/leaderboard", "get", operation("Users", "User leaderboards", vec![
+        query_param("count", false, "Number of entries in each leaderboard", json!({"type": "integer", "minimum": 0, "maximum": LEADERBOARD_MAX_COUNT, "default": 25})),
+    ], reference("Leaderboards"), &[("400", "BadRequest")], true));
+
+    add("/api/videos/{video_id}", "get", operation("Videos", "Video metadata", vec![path_param("video_id", "YouTube video ID")], reference("Video"), &[], true));
+    add("/api/videos/{video_id}/branding", "get", operation("Videos", "Submissions for a video, with the reason each one is or isn't displayed", vec![path_param("video_id", "YouTube video ID")], reference("VideoBranding"), &[], true));
+
+    add("/api/atom/recent", "get", atom_operation("Newest submissions", vec![], &[]));
+    add("/api/atom/user_id/{user_id}", "get", atom_operation("Newest submissions by a user", vec![path_param("user_id", "Public user ID")], &[("404", "NotFound")]));
+    add("/api/atom/video_id/{video_id}", "get", atom_operation("Newest submissions for a video", vec![path_param("video_id", "YouTube video ID")], &[("404", "NotFound")]));
+    add("/api/atom/channel/{channel}", "get", atom_operation("Newest submissions for videos of a channel", vec![path_param("channel", "Channel handle or UCID")], &[("333", "NotReadyYet"), ("404", "InnertubeDisabled")]));
+
+    let mut user_badge = badge_operation("Badge with statistics of a user", vec![path_param("user_id", "Public user ID"), path_param("badge", "Displayed statistic")]);
+    user_badge["parameters"][1]["schema"] = json!({"type": "string", "enum": ["titles", "thumbnails", "vip", "locked"]});
+    user_badge["description"] = json!("Users without submissions get a badge with zero counts.");
+    add("/api/badges/user_id/{user_id}/{badge}", "get", user_badge);
+    add("/api/badges/video_id/{video_id}/title", "get", badge_operation("Badge with the current top title of a video", vec![path_param("video_id", "YouTube video ID")]));
+
+    let mut innertube_video = operation("Innertube", "Video details fetched from YouTube", vec![path_param("video_id", "YouTube video ID")], reference("InnertubeVideo"), &[("404", "InnertubeDisabled")], false);
+    innertube_video["description"] = json!("Requires innertube to be enabled on the instance.");
+    innertube_video["responses"].as_object_mut().expect("responses should be an object").remove("503");
+    add("/innertube/video/{video_id}", "get", innertube_video);
+    let mut innertube_channel = channel_operation("Innertube", "Channel details fetched from YouTube", reference("InnertubeChannel"));
+    innertube_channel["parameters"] = json!([path_param("handle", "Channel handle or UCID")]);
+    add("/innertube/channel/{handle}", "get", innertube_channel);
+}
+
+fn sbserver_paths(paths: &mut Map<String, Value>) {
+    let sb_params = || vec![
+        query_param("service", false, "Only `YouTube` is supported", string()),
+        query_param("returnUserID", false, "Include submitter user IDs", json!({"type": "boolean", "default": false})),
+        query_param("fetchAll", false, "Include submissions that would normally be hidden due to low score", json!({"type": "boolean", "default": false})),
+    ];
+    let description = "SponsorBlockServer emulation, only available if enabled in the instance config. Returns 404 otherwise.";
+    let not_found = |schema: Value| json!({"description": "Unknown video or unsupported service", "content": json_content(schema)});
+
+    let mut video_params = vec![query_param("videoID", true, "YouTube video ID", string())];
+    video_params.extend(sb_params());
+    paths.insert("/sbserver/api/branding".to_owned(), json!({
+        "get": {
+            "tags": ["SponsorBlockServer emulation"],
+            "summary": "Branding for a video",
+            "description": description,
+            "parameters": video_params,
+            "responses": {
+                "200": {"description": "Success", "content": json_content(reference("SBApiVideo"))},
+                "304": error_ref("NotModified"),
+                "404": not_found(reference("SBApiVideo")),
+                "500": error_ref("Error"),
+                "503": error_ref("Unavailable"),
+            },
+        },
+        "post": {
+            "tags": ["SponsorBlockServer emulation"],
+            "summary": "Voting is not supported",
+            "responses": {"404": {"description": "Always returned", "content": {"text/plain": {"schema": string()}}}},
+        },
+    }));
+
+    let mut chunk_params = vec![path_param("hash_prefix", "First 4 hex characters of the SHA256 hash of the video ID")];
+    chunk_params.extend(sb_params());
+    paths.insert("/sbserver/api/branding/{hash_prefix}".to_owned(), json!({
+        "get": {
+            "tags": ["SponsorBlockServer emulation"],
+            "summary": "Branding for all videos with the given hash prefix",
+            "description": description,
+            "parameters": chunk_params,
+            "responses": {
+                "200": {"description": "Success", "content": json_content(json!({"type": "object", "additionalProperties": reference("SBApiVideo")}))},
+                "304": error_ref("NotModified"),
+                "400": error_ref("BadRequest"),
+                "404": not_found(json!({"type": "object"})),
+                "500": error_ref("Error"),
+                "503": error_ref("Unavailable"),
+            },
+        },
+    }));
+
+    paths.insert("/sbserver/api/userInfo".to_owned(), json!({
+        "get": {
+            "tags": ["SponsorBlockServer emulation"],
+            "summary": "User info",
+            "description": description,
+            "parameters": [query_param("publicUserID", true, "Public user ID", string())],
+            "responses": {
+                "200": {"description": "Success", "content": json_content(reference("SBUserInfo"))},
+                "304": error_ref("NotModified"),
+                "500": error_ref("Error"),
+                "503": error_ref("Unavailable"),
+            },
+        },
+    }));
+}
+
+fn document() -> Value {
+    let mut paths = Map::new();
+    api_paths(&mut paths);
+    sbserver_paths(&mut paths);
+    // every documented route belongs to a rate limited group
+    for path in paths.values_mut() {
+        for op in path.as_object_mut().into_iter().flat_map(|p| p.values_mut()) {
+            op["responses"]["429"] = error_ref("TooManyRequests");
+        }
+    }
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "DeArrow Browser",
+            "version": &**SERVER_VERSION,
+            "description": "The DeArrow Browser API is internal and may change at any time without a major version bump.",
+            "license": {"name": env!("CARGO_PKG_LICENSE"), "identifier": env!("CARGO_PKG_LICENSE")},
+        },
+        "paths": paths,
+        "webhooks": {
+            "reloadChanges": {
+                "post": {
+                    "summary": "Changes found by a reload that match the rules of a webhook from the server config",
+                    "description": "Retried with exponential backoff on network errors, 5xx and 429 responses.",
+                    "parameters": [{
+                        "name": WEBHOOK_SIGNATURE_HEADER,
+                        "in": "header",
+                        "required": true,
+                        "description": "`sha256=` followed by the hex encoded HMAC-SHA256 of the body, keyed with the secret of the webhook",
+                        "schema": string(),
+                    }],
+                    "requestBody": {"required": true, "content": json_content(reference("WebhookPayload"))},
+                    "responses": {"2XX": {"description": "The changes were received"}},
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas(),
+            "responses": responses(),
+            "securitySchemes": {
+                "token": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The auth secret, or one of the named tokens from the server config",
+                },
+            },
+        },
+    })
+}
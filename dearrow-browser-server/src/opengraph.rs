@@ -0,0 +1,173 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Serves the frontend's `index.html` with `OpenGraph` and Twitter meta tags describing the page,
+//! so that links pasted into chat apps get a useful preview.
+use std::{fmt::Write, sync::Arc};
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use dearrow_browser_api::sync::{ApiThumbnail, ApiTitle};
+use error_handling::ResContext;
+
+use crate::atom::escape;
+use crate::middleware::ETagCache;
+use crate::sbserver_emulation::top_title;
+use crate::state::{ConfigState, DBState};
+use crate::utils;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(video_page)
+       .service(user_page)
+       .service(uuid_page);
+}
+
+type PageResult = utils::Result<HttpResponse>;
+
+/// Contents of the meta tags of a page
+struct PageMeta {
+    title: String,
+    description: String,
+    /// Video whose thumbnail on youtube is used as the preview image
+    video_id: Option<Arc<str>>,
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 { format!("1 {noun}") } else { format!("{count} {noun}s") }
+}
+
+/// Fills the `<title>` of `index.html` and adds the meta tags to its `<head>`
+async fn render_page(req: &HttpRequest, config: &ConfigState, meta: Option<PageMeta>) -> PageResult {
+    let config = config.load();
+    let index_path = config.static_content_path.join("index.html");
+    let mut html = tokio::fs::read_to_string(&index_path).await.context("Failed to read index.html")?;
+    if let Some(meta) = meta {
+        // the Host header is chosen by the client, and the pages are cached
+        let url = format!("{}{}", config.public_origin(), req.uri().path());
+        let title = escape(&meta.title);
+        let description = escape(&meta.description);
+        let mut tags = format!(
+            "<meta name=\"description\" content=\"{description}\">\
+            <meta property=\"og:site_name\" content=\"DeArrow Browser\">\
+            <meta property=\"og:type\" content=\"website\">\
+            <meta property=\"og:url\" content=\"{}\">\
+            <meta property=\"og:title\" content=\"{title}\">\
+            <meta property=\"og:description\" content=\"{description}\">\
+            <meta name=\"twitter:title\" content=\"{title}\">\
+            <meta name=\"twitter:description\" content=\"{description}\">",
+            escape(&url),
+        );
+        if let Some(video_id) = meta.video_id {
+            let image = escape(&format!("https://i.ytimg.com/vi/{video_id}/hqdefault.jpg"));
+            write!(tags,
+                "<meta property=\"og:image\" content=\"{image}\">\
+                <meta name=\"twitter:image\" content=\"{image}\">\
+                <meta name=\"twitter:card\" content=\"summary_large_image\">"
+            ).context("Failed to render the meta tags")?;
+        } else {
+            tags.push_str("<meta name=\"twitter:card\" content=\"summary\">");
+        }
+        if let Some(start) = html.find("<title>") {
+            if let Some(len) = html[start..].find("</title>") {
+                html.replace_range(start + "<title>".len()..start + len, &format!("{title} - DeArrow Browser"));
+            }
+        }
+        if let Some(head_end) = html.find("</head>") {
+            html.insert_str(head_end, &tags);
+        }
+    }
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html))
+}
+
+#[get("/video_id/{video_id}", wrap = "ETagCache")]
+async fn video_page(req: HttpRequest, db_state: DBState, config: ConfigState, path: web::Path<String>) -> PageResult {
+    let meta = db_state.try_load().map(|db| {
+        let Some(video_id) = db.string_set.set.get(path.as_str()).cloned() else {
+            return PageMeta {
+                title: format!("Video {path}"),
+                description: "No titles or thumbnails were submitted for this video".to_owned(),
+                video_id: Some(path.as_str().into()),
+            };
+        };
+        let top_title = top_title(&db.db, &video_id);
+        let titles = db.db.titles.iter().filter(|t| Arc::ptr_eq(&t.video_id, &video_id)).count();
+        let thumbnails = db.db.thumbnails.iter().filter(|t| Arc::ptr_eq(&t.video_id, &video_id)).count();
+        PageMeta {
+            title: top_title.map_or_else(|| format!("Video {video_id}"), |t| t.title.to_string()),
+            description: format!("{} and {} submitted for video {video_id}", plural(titles, "title"), plural(thumbnails, "thumbnail")),
+            video_id: Some(video_id),
+        }
+    });
+    render_page(&req, &config, meta).await
+}
+
+#[get("/user_id/{user_id}", wrap = "ETagCache")]
+async fn user_page(req: HttpRequest, db_state: DBState, config: ConfigState, path: web::Path<String>) -> PageResult {
+    let meta = db_state.try_load().map(|db| {
+        let Some(user_id) = db.string_set.set.get(path.as_str()).cloned() else {
+            return PageMeta {
+                title: format!("User {path}"),
+                description: "This user has not submitted any titles or thumbnails".to_owned(),
+                video_id: None,
+            };
+        };
+        let titles = db.db.titles.iter().filter(|t| Arc::ptr_eq(&t.user_id, &user_id)).count();
+        let thumbnails = db.db.thumbnails.iter().filter(|t| Arc::ptr_eq(&t.user_id, &user_id)).count();
+        let vip = if db.db.vip_users.contains(&user_id) { " by a VIP" } else { "" };
+        PageMeta {
+            title: db.db.usernames.get(&user_id).map_or_else(|| format!("User {user_id}"), |u| u.username.to_string()),
+            description: format!("{} and {} submitted{vip}", plural(titles, "title"), plural(thumbnails, "thumbnail")),
+            video_id: None,
+        }
+    });
+    render_page(&req, &config, meta).await
+}
+
+#[get("/uuid/{uuid}", wrap = "ETagCache")]
+async fn uuid_page(req: HttpRequest, db_state: DBState, config: ConfigState, path: web::Path<String>) -> PageResult {
+    let meta = db_state.try_load().and_then(|db| {
+        let uuid = db.string_set.set.get(path.as_str()).cloned()?;
+        if let Some(title) = db.db.titles.iter().find(|t| Arc::ptr_eq(&t.uuid, &uuid)) {
+            let api = ApiTitle::from(title);
+            let details = [(api.locked, ", locked"), (api.unverified, ", unverified"), (api.removed || api.shadow_hidden, ", hidden")];
+            return Some(PageMeta {
+                title: title.title.to_string(),
+                description: format!(
+                    "Title for video {} with a score of {}{}",
+                    title.video_id, api.score,
+                    details.iter().filter(|(set, _)| *set).map(|(_, text)| *text).collect::<String>(),
+                ),
+                video_id: Some(title.video_id.clone()),
+            });
+        }
+        let thumb = db.db.thumbnails.iter().find(|t| Arc::ptr_eq(&t.uuid, &uuid))?;
+        let api = ApiThumbnail::from(thumb);
+        let details = [(api.locked, ", locked"), (api.removed || api.shadow_hidden, ", hidden")];
+        Some(PageMeta {
+            title: match thumb.timestamp {
+                Some(ts) => format!("Thumbnail at {ts}s"),
+                None => "Original thumbnail".to_owned(),
+            },
+            description: format!(
+                "Thumbnail for video {} with a score of {}{}",
+                thumb.video_id, api.score,
+                details.iter().filter(|(set, _)| *set).map(|(_, text)| *text).collect::<String>(),
+            ),
+            video_id: Some(thumb.video_id.clone()),
+        })
+    });
+    render_page(&req, &config, meta).await
+}
@@ -0,0 +1,182 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::HashMap, convert::Infallible, future::{ready, Ready}, net::{IpAddr, Ipv6Addr}, sync::{Mutex, PoisonError}, time::{Duration, Instant}};
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+
+use crate::constants::*;
+use crate::state::{AppConfig, ConfigState, RateLimit, RateLimitConfig};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    map: HashMap<IpAddr, Bucket>,
+    /// Full buckets are removed once the map grows to this size
+    prune_at: usize,
+}
+
+/// Token bucket rate limiter, keeping a separate bucket for each client
+pub struct RateLimiter {
+    limit: Option<RateLimit>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: Option<RateLimit>) -> RateLimiter {
+        RateLimiter {
+            limit,
+            buckets: Mutex::new(Buckets { map: HashMap::new(), prune_at: RATELIMIT_PRUNE_THRESHOLD }),
+        }
+    }
+
+    /// Takes a token from the client's bucket, or returns how long the client has to wait for the next one
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        let Some(ref limit) = self.limit else { return Ok(()) };
+        let capacity = f64::from(limit.requests);
+        let rate = capacity / limit.period_secs;
+        let now = Instant::now();
+        let refill = |bucket: &Bucket| (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(capacity);
+
+        // the buckets only hold numbers, a panic while holding the lock can't leave them in an invalid state
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.map.len() >= buckets.prune_at {
+            buckets.map.retain(|_, b| refill(b) < capacity);
+            buckets.prune_at = (buckets.map.len() * 2).max(RATELIMIT_PRUNE_THRESHOLD);
+        }
+        let bucket = buckets.map.entry(client).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            // limits that never refill are rejected when loading the config, but don't panic if one gets here anyway
+            Err(Duration::try_from_secs_f64((1. - bucket.tokens) / rate).unwrap_or(RATELIMIT_FALLBACK_RETRY_AFTER))
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum RateLimitGroup {
+    Api,
+    SBServer,
+    Innertube,
+}
+
+pub struct RateLimiters {
+    pub api: RateLimiter,
+    pub sbserver: RateLimiter,
+    pub innertube: RateLimiter,
+    /// Limits starting new channel fetches, which send many requests to youtube
+    pub channel_fetches: RateLimiter,
+}
+
+impl RateLimiters {
+    pub fn new(config: &RateLimitConfig) -> RateLimiters {
+        RateLimiters {
+            api: RateLimiter::new(config.api.clone()),
+            sbserver: RateLimiter::new(config.sbserver.clone()),
+            innertube: RateLimiter::new(config.innertube.clone()),
+            channel_fetches: RateLimiter::new(config.channel_fetches.clone()),
+        }
+    }
+
+    pub fn group(&self, group: RateLimitGroup) -> &RateLimiter {
+        match group {
+            RateLimitGroup::Api => &self.api,
+            RateLimitGroup::SBServer => &self.sbserver,
+            RateLimitGroup::Innertube => &self.innertube,
+        }
+    }
+}
+
+/// Address of the client, taken from the trusted proxy header if configured.
+///
+/// IPv6 addresses are truncated to their /64 prefix, since a single client usually controls the whole prefix.
+/// `None` if the address could not be determined, such as for unix socket connections without a proxy header.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl ClientIp {
+    pub fn from_request(req: &HttpRequest, config: &AppConfig) -> ClientIp {
+        // the proxy appends the address it sees to the end of the list.
+        // requests without the header didn't go through the proxy, so they're limited by the connecting peer instead
+        let addr = config.rate_limits.trusted_proxy_header.as_ref()
+            .and_then(|header| req.headers().get(header.as_str()))
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.rsplit(',').next())
+            .and_then(|h| h.trim().parse().ok())
+            .or_else(|| req.peer_addr().map(|a| a.ip()));
+        ClientIp(addr.map(|addr| match addr {
+            IpAddr::V4(..) => addr,
+            IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from_bits(v6.to_bits() & !u128::from(u64::MAX))),
+        }))
+    }
+}
+
+impl FromRequest for ClientIp {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = req.app_data::<ConfigState>().expect("ConfigHandle should be registered as app data");
+        ready(Ok(ClientIp::from_request(req, &config.load())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use actix_web::test::TestRequest;
+
+    use super::{ClientIp, RateLimiter};
+    use crate::constants::RATELIMIT_FALLBACK_RETRY_AFTER;
+    use crate::state::{AppConfig, RateLimit};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const CLIENT_BEHIND_PROXY: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+
+    #[test]
+    fn limit_without_refill_does_not_panic() {
+        let limiter = RateLimiter::new(Some(RateLimit { requests: 0, period_secs: 60. }));
+        assert_eq!(limiter.check(CLIENT), Err(RATELIMIT_FALLBACK_RETRY_AFTER));
+        let limiter = RateLimiter::new(Some(RateLimit { requests: 1, period_secs: -60. }));
+        assert_eq!(limiter.check(CLIENT), Ok(()));
+        assert_eq!(limiter.check(CLIENT), Err(RATELIMIT_FALLBACK_RETRY_AFTER));
+    }
+
+    #[test]
+    fn missing_proxy_header_falls_back_to_peer() {
+        let mut config = AppConfig::default();
+        config.rate_limits.trusted_proxy_header = Some("X-Forwarded-For".to_owned());
+        let peer = "192.0.2.1:1234".parse().unwrap();
+
+        let req = TestRequest::default().peer_addr(peer).to_http_request();
+        assert_eq!(ClientIp::from_request(&req, &config).0, Some(peer.ip()));
+
+        let req = TestRequest::default().peer_addr(peer).insert_header(("X-Forwarded-For", "not an address")).to_http_request();
+        assert_eq!(ClientIp::from_request(&req, &config).0, Some(peer.ip()));
+
+        let req = TestRequest::default().peer_addr(peer).insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7")).to_http_request();
+        assert_eq!(ClientIp::from_request(&req, &config).0, Some(CLIENT_BEHIND_PROXY));
+    }
+}
@@ -0,0 +1,219 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, MutexGuard, PoisonError}, time::{Duration, Instant}};
+
+use chrono::Utc;
+use dearrow_browser_api::sync::{ReloadJobStatus, ReloadOutcome, ReloadPhase, ReloadPhaseProgress};
+use dearrow_parser::{LoadPhase, LoadProgress};
+
+struct PhaseRecord {
+    phase: ReloadPhase,
+    started: Instant,
+    /// `None` while the phase is still running
+    result: Option<(u64, Duration)>,
+}
+
+struct JobState {
+    phases: Vec<PhaseRecord>,
+    outcome: ReloadOutcome,
+    error: Option<String>,
+    duration: Option<Duration>,
+}
+
+/// A single database reload, tracking the progress of each phase
+pub struct ReloadJob {
+    pub id: u64,
+    pub requested_by: String,
+    started: i64,
+    start: Instant,
+    /// Rows read in the current phase
+    rows: AtomicU64,
+    state: Mutex<JobState>,
+}
+
+impl ReloadJob {
+    // the state only holds progress information, a panic while holding the lock can't leave it in an invalid state
+    fn state(&self) -> MutexGuard<'_, JobState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn finish_phase(&self, state: &mut JobState) {
+        if let Some(last) = state.phases.last_mut() {
+            if last.result.is_none() {
+                last.result = Some((self.rows.swap(0, Ordering::Relaxed), last.started.elapsed()));
+            }
+        }
+    }
+
+    pub fn start_phase(&self, phase: ReloadPhase) {
+        let mut state = self.state();
+        self.finish_phase(&mut state);
+        state.phases.push(PhaseRecord { phase, started: Instant::now(), result: None });
+    }
+
+    pub fn finish(&self, error: Option<String>) {
+        self.finish_locked(&mut self.state(), error);
+    }
+
+    fn finish_locked(&self, state: &mut JobState, error: Option<String>) {
+        self.finish_phase(state);
+        state.outcome = if error.is_some() { ReloadOutcome::Failed } else { ReloadOutcome::Succeeded };
+        state.error = error;
+        state.duration = Some(self.start.elapsed());
+    }
+
+    /// Returns a guard marking the job as failed if it's dropped before [`ReloadJob::finish`] is called,
+    /// so that a panicking reload doesn't block all further reloads
+    pub fn fail_if_unfinished(&self) -> UnfinishedGuard<'_> {
+        UnfinishedGuard(self)
+    }
+
+    pub fn status(&self) -> ReloadJobStatus {
+        let state = self.state();
+        ReloadJobStatus {
+            id: self.id,
+            requested_by: self.requested_by.as_str().into(),
+            started: self.started,
+            elapsed_ms: duration_ms(state.duration.unwrap_or_else(|| self.start.elapsed())),
+            phases: state.phases.iter().map(|p| {
+                let (rows, elapsed) = p.result.unwrap_or_else(|| (self.rows.load(Ordering::Relaxed), p.started.elapsed()));
+                ReloadPhaseProgress {
+                    phase: p.phase,
+                    rows,
+                    elapsed_ms: duration_ms(elapsed),
+                    finished: p.result.is_some(),
+                }
+            }).collect(),
+            outcome: state.outcome,
+            error: state.error.as_deref().map(Into::into),
+        }
+    }
+}
+
+pub struct UnfinishedGuard<'a>(&'a ReloadJob);
+
+impl Drop for UnfinishedGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state();
+        if state.outcome == ReloadOutcome::Running {
+            self.0.finish_locked(&mut state, Some("The reload stopped unexpectedly".to_owned()));
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+impl LoadProgress for ReloadJob {
+    fn phase_started(&self, phase: LoadPhase) {
+        self.start_phase(match phase {
+            LoadPhase::Thumbnails => ReloadPhase::Thumbnails,
+            LoadPhase::Titles => ReloadPhase::Titles,
+            LoadPhase::Usernames => ReloadPhase::Usernames,
+            LoadPhase::VipUsers => ReloadPhase::VipUsers,
+            LoadPhase::SponsorTimes => ReloadPhase::SponsorTimes,
+            LoadPhase::Warnings => ReloadPhase::Warnings,
+        });
+    }
+
+    fn row_read(&self) {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Hands out reload job IDs and keeps the most recent job
+#[derive(Default)]
+pub struct ReloadJobs {
+    next_id: AtomicU64,
+    latest: Mutex<Option<Arc<ReloadJob>>>,
+}
+
+fn is_running(job: Option<&Arc<ReloadJob>>) -> bool {
+    job.is_some_and(|job| job.state().outcome == ReloadOutcome::Running)
+}
+
+impl ReloadJobs {
+    fn latest_lock(&self) -> MutexGuard<'_, Option<Arc<ReloadJob>>> {
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Creates a new job, unless another one is still running
+    pub fn start(&self, requested_by: String) -> Option<Arc<ReloadJob>> {
+        let mut latest = self.latest_lock();
+        if is_running(latest.as_ref()) {
+            return None;
+        }
+        let job = Arc::new(ReloadJob {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            requested_by,
+            started: Utc::now().timestamp_millis(),
+            start: Instant::now(),
+            rows: AtomicU64::new(0),
+            state: Mutex::new(JobState {
+                phases: Vec::with_capacity(ReloadPhase::ALL.len()),
+                outcome: ReloadOutcome::Running,
+                error: None,
+                duration: None,
+            }),
+        });
+        *latest = Some(job.clone());
+        Some(job)
+    }
+
+    pub fn latest(&self) -> Option<Arc<ReloadJob>> {
+        self.latest_lock().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        is_running(self.latest_lock().as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use dearrow_browser_api::sync::ReloadOutcome;
+
+    use super::ReloadJobs;
+
+    #[test]
+    fn panicking_reload_does_not_block_the_next_one() {
+        let jobs = ReloadJobs::default();
+        let job = jobs.start("a test".to_owned()).unwrap();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _guard = job.fail_if_unfinished();
+            panic!("the reload panicked");
+        }));
+        assert!(result.is_err());
+        assert!(job.status().outcome == ReloadOutcome::Failed);
+        assert!(jobs.start("a test".to_owned()).is_some());
+    }
+
+    #[test]
+    fn finished_reload_keeps_its_outcome() {
+        let jobs = ReloadJobs::default();
+        let job = jobs.start("a test".to_owned()).unwrap();
+        {
+            let _guard = job.fail_if_unfinished();
+            job.finish(None);
+        }
+        assert!(job.status().outcome == ReloadOutcome::Succeeded);
+    }
+}
@@ -16,28 +16,40 @@
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 #![allow(clippy::needless_pass_by_value)]
-use std::{collections::HashSet, sync::Arc};
+use std::{cmp::Ordering, collections::{HashMap, HashSet}, fmt::Write, sync::Arc, time::Instant};
 use actix_web::Either;
-use actix_web::{Responder, get, post, web, http::StatusCode, HttpResponse, rt::task::spawn_blocking};
-use error_handling::{anyhow, bail, ErrorContext, IntoErrorIterator, ResContext, SerializableError};
+use actix_web::{Responder, get, post, web, http::{header::{CacheControl, CacheDirective, ContentEncoding, RETRY_AFTER}, StatusCode}, HttpRequest, HttpResponse, rt::task::spawn_blocking};
+use error_handling::{anyhow, ErrorContext, IntoErrorIterator, ResContext, SerializableError};
 use chrono::Utc;
-use dearrow_parser::{DearrowDB, ThumbnailFlags, TitleFlags};
+use dearrow_parser::{DearrowDB, StringSet, ThumbnailFlags, TitleFlags};
 use dearrow_browser_api::sync::{*, self as api};
 use futures::join;
 use log::warn;
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 
+use crate::atom;
+use crate::badges;
+use crate::auth::authorize;
 use crate::built_info;
 use crate::constants::*;
+use crate::feed::{ChannelFilter, FeedEvent, FeedFilter, SubmissionFeed};
+use crate::formats::Negotiated;
+use crate::metrics::METRICS;
 use crate::middleware::{ETagCache, ETagCacheControl};
-use crate::sbserver_emulation::get_random_time_for_video;
+use crate::openapi::OPENAPI_DOCUMENT;
+use crate::ratelimit::ClientIp;
+use crate::reload::{ReloadJob, ReloadJobs};
+use crate::sbserver_emulation::{get_random_time_for_video, thumbnail_hidden_reason, thumbnail_rank, title_hidden_reason, title_rank};
+use crate::sqlite_export;
 use crate::state::*;
 use crate::utils::{self, ExtendResponder, ResponderExt};
+use crate::webhooks::Webhooks;
 
-pub fn configure(app_config: web::Data<AppConfig>) -> impl FnOnce(&mut web::ServiceConfig) {
+pub fn configure(app_config: Arc<AppConfig>) -> impl FnOnce(&mut web::ServiceConfig) {
     return move |cfg| {
         cfg.service(helo)
+           .service(get_health)
+           .service(get_readiness)
            .service(get_titles)
            .service(get_unverified_titles)
            .service(get_broken_titles)
@@ -50,12 +62,21 @@ pub fn configure(app_config: web::Data<AppConfig>) -> impl FnOnce(&mut web::Serv
            .service(get_thumbnails_by_video_id)
            .service(get_thumbnails_by_user_id)
            .service(get_user_by_userid)
+           .service(search_users)
            .service(get_user_warnings)
            .service(get_issued_warnings)
+           .service(get_leaderboard)
            .service(get_video)
+           .service(get_video_branding)
            .service(get_status)
            .service(get_errors)
-           .service(request_reload);
+           .service(request_reload)
+           .service(reset_channel_cache)
+           .service(reload_config)
+           .service(get_feed)
+           .service(get_openapi_document)
+           .service(web::scope("/atom").configure(atom::configure(app_config.clone())))
+           .service(web::scope("/badges").configure(badges::configure));
 
         if app_config.innertube.enable {
             cfg.service(get_titles_by_channel)
@@ -67,8 +88,8 @@ pub fn configure(app_config: web::Data<AppConfig>) -> impl FnOnce(&mut web::Serv
     };
 }
 
-type JsonResult<T> = utils::Result<web::Json<T>>;
-type JsonResultOrFetchProgress<T> = utils::Result<Either<web::Json<T>, (ExtendResponder<web::Json<api::ChannelFetchProgress>>, StatusCode)>>;
+type ApiResult<T> = utils::Result<Negotiated<T>>;
+type ApiResultOrFetchProgress<T> = utils::Result<Either<Negotiated<T>, (ExtendResponder<Negotiated<api::ChannelFetchProgress>>, StatusCode)>>;
 
 #[derive(Deserialize)]
 #[serde(default)]
@@ -86,7 +107,7 @@ impl Default for MainEndpointURLParams {
     }
 }
 
-async fn innertube_disabled() -> HttpResponse {
+pub async fn innertube_disabled() -> HttpResponse {
     HttpResponse::NotFound().body("This endpoint requires making requests to innertube, which is disabled on this DeArrow Browser instance.")
 }
 
@@ -95,28 +116,42 @@ async fn helo() -> impl Responder {
     "hi"
 }
 
-#[get("/status")]
-async fn get_status(db_lock: DBLock, string_set: StringSetLock, config: web::Data<AppConfig>) -> JsonResult<StatusResponse> {
-    let strings = match string_set.try_read() {
-        Err(_) => None,
-        Ok(set) => Some(set.set.len()),
-    };
-    let channel_cache = {
-        let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-        db.channel_cache.clone()
+/// Liveness check, responds as soon as the server is up
+#[get("/health")]
+async fn get_health() -> impl Responder {
+    "ok"
+}
+
+/// Readiness check, responds with 503 Service Unavailable until the database is loaded
+#[get("/ready")]
+async fn get_readiness(req: HttpRequest, db_state: DBState, jobs: web::Data<ReloadJobs>) -> HttpResponse {
+    let response = ReadinessResponse {
+        ready: db_state.try_load().is_some(),
+        reload: jobs.latest().map(|job| job.status()),
     };
-    let (cached_channels, fscached_channels) = join!(channel_cache.num_channels_cached(), channel_cache.num_channels_fscached());
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(StatusResponse {
+    if response.ready {
+        Negotiated(response).into_response(&req, StatusCode::OK)
+    } else {
+        let mut resp = Negotiated(response).into_response(&req, StatusCode::SERVICE_UNAVAILABLE);
+        resp.headers_mut().insert(RETRY_AFTER, DB_UNAVAILABLE_RETRY_AFTER.as_secs().into());
+        resp
+    }
+}
+
+#[get("/status")]
+async fn get_status(db_state: DBState, config: ConfigState, jobs: web::Data<ReloadJobs>) -> ApiResult<StatusResponse> {
+    let db = db_state.load()?;
+    let (cached_channels, fscached_channels) = join!(db.channel_cache.num_channels_cached(), db.channel_cache.num_channels_fscached());
+    Ok(Negotiated(StatusResponse {
         last_updated: db.last_updated,
         last_modified: db.last_modified,
-        updating_now: db.updating_now,
+        updating_now: jobs.is_running(),
         titles: db.db.titles.len(),
         thumbnails: db.db.thumbnails.len(),
         vip_users: db.db.vip_users.len(),
         usernames: db.db.usernames.len(),
         errors: db.errors.len(),
-        string_count: strings,
+        string_count: Some(db.string_set.set.len()),
         video_infos: db.video_info_count,
         uncut_segments: db.uncut_segment_count,
         cached_channels,
@@ -125,95 +160,215 @@ async fn get_status(db_lock: DBLock, string_set: StringSetLock, config: web::Dat
         server_git_hash: SERVER_GIT_HASH.clone(),
         server_git_dirty: built_info::GIT_DIRTY,
         server_build_timestamp: *BUILD_TIMESTAMP,
-        server_startup_timestamp: config.startup_timestamp.timestamp(),
+        server_startup_timestamp: config.load().startup_timestamp.timestamp(),
+        reload: jobs.latest().map(|job| job.status()),
     }))
 }
 
 #[derive(Deserialize, Debug)]
-struct Auth {
-    auth: Option<String>
+struct ReloadQuery {
+    auth: Option<String>,
+    /// Respond only after the reload finishes
+    #[serde(default)]
+    wait: bool,
 }
 
-fn do_reload(db_lock: DBLock, string_set_lock: StringSetLock, config: web::Data<AppConfig>) -> Result<(), ErrorContext> {
-    {
-        let mut db_state = db_lock.write().map_err(|_| DB_WRITE_ERR.clone())?;
-        if db_state.updating_now {
-            bail!("Already updating!");
-        }
-        db_state.updating_now = true;
+/// Creates a job for the reload, failing with 409 Conflict if a reload is already running
+pub fn begin_reload(jobs: &ReloadJobs, requested_by: String) -> utils::Result<Arc<ReloadJob>> {
+    let Some(job) = jobs.start(requested_by) else {
+        return Err(utils::Error::from(anyhow!("A reload is already in progress")).set_status(StatusCode::CONFLICT));
+    };
+    warn!("Reload #{} requested by {}", job.id, job.requested_by);
+    Ok(job)
+}
+
+/// Runs a reload job created by [`begin_reload`]
+pub fn do_reload(job: &ReloadJob, db_state: &DBState, config: &AppConfig, feed: &SubmissionFeed, webhooks: &Webhooks) -> Result<(), ErrorContext> {
+    let _guard = job.fail_if_unfinished();
+    feed.send(FeedEvent::ReloadStarted(ReloadStartedEvent { timestamp: Utc::now().timestamp_millis() }));
+    let start = Instant::now();
+    let result = load_new_database(job, db_state, config);
+    METRICS.record_reload(start.elapsed(), result.is_ok());
+    if let Err(ref e) = result {
+        feed.send(FeedEvent::ReloadFailed(ReloadFailedEvent { error: format!("{e:?}").into() }));
+        warn!("Reload #{} failed: {e:?}", job.id);
+    }
+    job.finish(result.as_ref().err().map(|e| format!("{e:?}")));
+    let (old_state, new_state) = result?;
+    // diffing the databases can take a while, so it's done once the reload is reported as finished
+    if feed.has_subscribers() {
+        feed.send_reload_finished(new_state.last_updated, old_state.as_ref().map(|s| &*s.db), &new_state.db);
+    }
+    if let Some(old_state) = old_state {
+        webhooks.report_reload(&old_state.db, new_state.clone());
+    }
+    // a running export keeps its snapshot alive, which would defeat dropping the old database before the next reload
+    if config.sqlite_export && !config.low_memory_reload {
+        // the new database is already being served, so the export doesn't delay or fail the reload
+        sqlite_export::export_in_background(job.id, new_state, config.cache_path.join(SQLITE_EXPORT_FILE));
     }
-    warn!("Reload requested");
-    let mut string_set_clone = string_set_lock.read().map_err(|_| SS_READ_ERR.clone())?.clone();
-    let (mut new_db, errors) = DearrowDB::load_dir(config.mirror_path.as_path(), &mut string_set_clone)?;
-    new_db.sort();
-    let last_updated = Utc::now().timestamp_millis();
-    let last_modified = utils::get_mtime(&config.mirror_path.join("titles.csv"));
-    {
-        let mut string_set = string_set_lock.write().map_err(|_| SS_WRITE_ERR.clone())?;
-        let mut db_state = db_lock.write().map_err(|_| DB_WRITE_ERR.clone())?;
-        *string_set = string_set_clone;
-        *db_state = DatabaseState {
-            db: new_db,
-            errors: errors.into(),
-            last_updated,
-            last_modified,
-            updating_now: false,
-            etag: None,
-            channel_cache: db_state.channel_cache.reset(),
-            uncut_segment_count: 0,
-            video_info_count: 0,
-        };
-        db_state.uncut_segment_count = db_state.calculate_uncut_segment_count();
-        db_state.video_info_count = db_state.calculate_video_info_count();
-        db_state.etag = Some(db_state.generate_etag());
-        string_set.clean();
-    }
-    warn!("Reload finished");
     Ok(())
 }
 
-#[post("/reload")]
-async fn request_reload(db_lock: DBLock, string_set_lock: StringSetLock, config: web::Data<AppConfig>, auth: web::Query<Auth>) -> HttpResponse {
-    let provided_hash = match auth.auth.as_deref() {
-        None => { return HttpResponse::NotFound().finish(); },
-        Some(s) => Sha256::digest(s),
+/// Returns the old snapshot, unless it was dropped before the reload, and the new one
+fn load_new_database(job: &ReloadJob, db_state: &DBState, config: &AppConfig) -> Result<(Option<Arc<DatabaseState>>, Arc<DatabaseState>), ErrorContext> {
+    let (old_state, mut string_set) = match db_state.try_load() {
+        Some(old_state) if !config.low_memory_reload => {
+            let string_set = (*old_state.string_set).clone();
+            (Some(old_state), string_set)
+        },
+        old_state => {
+            if old_state.is_some() {
+                // requests already holding the old snapshot may still keep it alive for a moment
+                warn!("Dropping the old database before reloading, requests will fail until the reload finishes");
+                drop(db_state.take());
+            }
+            (None, StringSet::with_capacity(16384))
+        },
     };
-    let actual_hash = Sha256::digest(config.auth_secret.as_str());
+    let (mut new_db, errors) = DearrowDB::load_dir_with_progress(config.mirror_path.as_path(), &mut string_set, job)?;
+    job.start_phase(ReloadPhase::Indexing);
+    new_db.sort();
+    string_set.clean();
+    let string_set = Arc::new(string_set);
+    let channel_cache = db_state.new_channel_cache(string_set.clone());
+    let new_state = DatabaseState::new(new_db, string_set, errors, utils::get_mtime(&config.mirror_path.join("titles.csv")), channel_cache);
+    job.start_phase(ReloadPhase::Swap);
+    let new_state = Arc::new(new_state);
+    db_state.store(new_state.clone());
+    warn!("Reload #{} finished", job.id);
+    Ok((old_state, new_state))
+}
 
-    if provided_hash != actual_hash {
-        return HttpResponse::Forbidden().finish();
+#[post("/reload")]
+async fn request_reload(req: HttpRequest, db_state: DBState, config: ConfigState, feed: web::Data<SubmissionFeed>, webhooks: web::Data<Webhooks>, jobs: web::Data<ReloadJobs>, query: web::Query<ReloadQuery>) -> utils::Result<HttpResponse> {
+    let config = config.load();
+    let requested_by = format!("the '{}' token", authorize(&req, &config, TokenScope::Reload, query.auth.as_deref())?);
+    let job = begin_reload(&jobs, requested_by)?;
+    let task = {
+        let job = job.clone();
+        spawn_blocking(move || do_reload(&job, &db_state, &config, &feed, &webhooks))
+    };
+    if !query.wait {
+        return Ok(Negotiated(job.status()).into_response(&req, StatusCode::ACCEPTED));
+    }
+    match task.await {
+        Ok(Ok(())) => Ok(Negotiated(job.status()).into_response(&req, StatusCode::OK)),
+        Ok(Err(..)) => Ok(Negotiated(job.status()).into_response(&req, StatusCode::INTERNAL_SERVER_ERROR)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(format!("{e:?}"))),
     }
-    match spawn_blocking(move || do_reload(db_lock, string_set_lock, config)).await {
-        Ok(..) => HttpResponse::Ok().body("Reload complete"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("{e:?}")),
+}
+
+#[post("/channel_cache/reset")]
+async fn reset_channel_cache(req: HttpRequest, db_state: DBState, config: ConfigState) -> utils::Result<HttpResponse> {
+    let config = config.load();
+    let token = authorize(&req, &config, TokenScope::CacheAdmin, None)?;
+    db_state.update(|db| DatabaseState {
+        channel_cache: db.channel_cache.reset(db.string_set.clone()),
+        ..db.clone()
+    });
+    warn!("Channel cache reset using the '{token}' token");
+    Ok(HttpResponse::Ok().body("Channel cache reset"))
+}
+
+#[post("/config/reload")]
+async fn reload_config(req: HttpRequest, config: ConfigState) -> utils::Result<HttpResponse> {
+    let token = authorize(&req, &config.load(), TokenScope::Reload, None)?.to_owned();
+    warn!("Config reload requested by the '{token}' token");
+    let ignored = config.reload()?;
+    let mut body = "Config reloaded".to_owned();
+    for setting in ignored {
+        write!(body, "\nThe {setting} setting can't be changed while the server is running, restart the server to apply the new value").expect("Writing to a String should not fail");
     }
+    Ok(HttpResponse::Ok().body(body))
 }
 
 #[get("/errors")]
-async fn get_errors(db_lock: DBLock) -> JsonResult<Vec<SerializableError>> {
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(db.errors.iter().map(IntoErrorIterator::serializable_copy).collect()))
+async fn get_errors(db_state: DBState) -> ApiResult<Vec<SerializableError>> {
+    let db = db_state.load()?;
+    Ok(Negotiated(db.errors.iter().map(IntoErrorIterator::serializable_copy).collect()))
+}
+
+#[get("/openapi.json")]
+async fn get_openapi_document() -> web::Json<&'static serde_json::Value> {
+    web::Json(&OPENAPI_DOCUMENT)
+}
+
+#[derive(Deserialize)]
+struct FeedURLParams {
+    video_id: Option<String>,
+    user_id: Option<String>,
+    channel: Option<String>,
+    #[serde(default = "default_feed_submissions")]
+    submissions: bool,
+}
+
+fn default_feed_submissions() -> bool {
+    true
+}
+
+#[get("/feed")]
+async fn get_feed(client: ClientIp, db_state: DBState, config: ConfigState, feed: web::Data<SubmissionFeed>, query: web::Query<FeedURLParams>) -> utils::Result<Either<HttpResponse, (ExtendResponder<Negotiated<api::ChannelFetchProgress>>, StatusCode)>> {
+    let query = query.into_inner();
+    let channel = match query.channel {
+        None => None,
+        Some(_) if !config.load().innertube.enable => {
+            return Err(utils::Error::from(anyhow!("Filtering by channel requires making requests to innertube, which is disabled on this DeArrow Browser instance.")).set_status(StatusCode::NOT_FOUND));
+        },
+        Some(channel) => {
+            let channel_cache = {
+                let db = db_state.load()?;
+                db.channel_cache.clone()
+            };
+            match channel_cache.get_channel(&channel, client).await.context("Failed to get channel info")? {
+                GetChannelOutput::Pending(progress) => {
+                    let mut resp = Negotiated(api::ChannelFetchProgress::from(&progress)).extend();
+                    resp.extensions.insert(ETagCacheControl::DoNotCache);
+                    return Ok(Either::Right((resp, *NOT_READY_YET)));
+                },
+                GetChannelOutput::RateLimited(retry_after) => return Err(utils::Error::RateLimited(retry_after)),
+                GetChannelOutput::Resolved(data) => Some(ChannelFilter {
+                    channel,
+                    video_ids: data.video_ids.iter().cloned().collect(),
+                }),
+            }
+        },
+    };
+    let filter = FeedFilter {
+        video_id: query.video_id,
+        user_id: query.user_id,
+        channel,
+        submissions: query.submissions,
+    };
+    Ok(Either::Left(
+        HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+            // the compression middleware would hold events back until enough data accumulates
+            .insert_header(ContentEncoding::Identity)
+            .streaming(feed.subscribe(filter, db_state.clone()))
+    ))
 }
 
 #[get("/titles", wrap = "ETagCache")]
-async fn get_titles(db_lock: DBLock, query: web::Query<MainEndpointURLParams>) -> JsonResult<Vec<ApiTitle>> {
+async fn get_titles(db_state: DBState, query: web::Query<MainEndpointURLParams>) -> ApiResult<Vec<ApiTitle>> {
     if query.count > 1024 {
         return Err(
             utils::Error::from(anyhow!("Too many requested titles. You requested {} titles, but the configured max is 1024.", query.count))
                 .set_status(StatusCode::BAD_REQUEST)
         );
     }
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+    let db = db_state.load()?;
+    Ok(Negotiated(
         db.db.titles.iter().rev().skip(query.offset).take(query.count)
             .map(|t| t.into_with_db(&db.db)).collect::<Vec<_>>()
     ))
 }
 
 #[get("/titles/unverified", wrap = "ETagCache")]
-async fn get_unverified_titles(db_lock: DBLock) -> JsonResult<Vec<ApiTitle>> {
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+async fn get_unverified_titles(db_state: DBState) -> ApiResult<Vec<ApiTitle>> {
+    let db = db_state.load()?;
+    Ok(Negotiated(
         db.db.titles.iter().rev()
             .filter(|t| t.flags.contains(TitleFlags::Unverified) && !t.flags.intersects(TitleFlags::Locked | TitleFlags::ShadowHidden | TitleFlags::Removed) && t.votes-t.downvotes > -1)
             .map(|t| t.into_with_db(&db.db)).collect::<Vec<_>>()
@@ -221,9 +376,9 @@ async fn get_unverified_titles(db_lock: DBLock) -> JsonResult<Vec<ApiTitle>> {
 }
 
 #[get("/titles/broken", wrap = "ETagCache")]
-async fn get_broken_titles(db_lock: DBLock) -> JsonResult<Vec<ApiTitle>> {
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+async fn get_broken_titles(db_state: DBState) -> ApiResult<Vec<ApiTitle>> {
+    let db = db_state.load()?;
+    Ok(Negotiated(
         db.db.titles.iter().rev()
             .filter(|t| t.flags.contains(TitleFlags::MissingVotes))
             .map(|t| t.into_with_db(&db.db)).collect::<Vec<_>>()
@@ -231,13 +386,12 @@ async fn get_broken_titles(db_lock: DBLock) -> JsonResult<Vec<ApiTitle>> {
 }
 
 #[get("/titles/uuid/{uuid}", wrap = "ETagCache")]
-async fn get_title_by_uuid(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<ApiTitle> {
-    let Some(uuid) = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-            .set.get(path.into_inner().as_str()).cloned() else {
+async fn get_title_by_uuid(db_state: DBState, path: web::Path<String>) -> ApiResult<ApiTitle> {
+    let db = db_state.load()?;
+    let Some(uuid) = db.string_set.set.get(path.into_inner().as_str()).cloned() else {
         return Err(utils::Error::EmptyStatus(StatusCode::NOT_FOUND));
     };
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+    Ok(Negotiated(
         db.db.titles.iter().find(|t| Arc::ptr_eq(&t.uuid, &uuid))
             .map(|t| t.into_with_db(&db.db))
             .ok_or(utils::Error::EmptyStatus(StatusCode::NOT_FOUND))?
@@ -245,10 +399,9 @@ async fn get_title_by_uuid(db_lock: DBLock, string_set: StringSetLock, path: web
 }
 
 #[get("/titles/video_id/{video_id}", wrap = "ETagCache")]
-async fn get_titles_by_video_id(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiTitle>> {
-    let video_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.into_inner().as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_titles_by_video_id(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiTitle>> {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.into_inner().as_str()).cloned();
     let titles = match video_id {
         None => vec![],
         Some(id) => db.db.titles.iter().rev()
@@ -256,14 +409,13 @@ async fn get_titles_by_video_id(db_lock: DBLock, string_set: StringSetLock, path
             .map(|t| t.into_with_db(&db.db))
             .collect(),
     };
-    Ok(web::Json(titles))
+    Ok(Negotiated(titles))
 }
 
 #[get("/titles/user_id/{user_id}", wrap = "ETagCache")]
-async fn get_titles_by_user_id(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiTitle>> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.into_inner().as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_titles_by_user_id(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiTitle>> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.into_inner().as_str()).cloned();
     let titles = match user_id {
         None => vec![],
         Some(id) => db.db.titles.iter().rev()
@@ -271,56 +423,57 @@ async fn get_titles_by_user_id(db_lock: DBLock, string_set: StringSetLock, path:
             .map(|t| t.into_with_db(&db.db))
             .collect(),
     };
-    Ok(web::Json(titles))
+    Ok(Negotiated(titles))
 }
 
 #[get("/titles/channel/{channel}", wrap = "ETagCache")]
-async fn get_titles_by_channel(db_lock: DBLock, path: web::Path<String>) -> JsonResultOrFetchProgress<Vec<ApiTitle>> {
+async fn get_titles_by_channel(client: ClientIp, db_state: DBState, path: web::Path<String>) -> ApiResultOrFetchProgress<Vec<ApiTitle>> {
     let channel_cache = {
-        let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+        let db = db_state.load()?;
         db.channel_cache.clone()
     };
-    let channel_data = channel_cache.get_channel(path.into_inner().as_str()).await.context("Failed to get channel info")?;
+    let channel_data = channel_cache.get_channel(path.into_inner().as_str(), client).await.context("Failed to get channel info")?;
 
     match channel_data {
         GetChannelOutput::Pending(progress) => {
-            let mut resp = web::Json(api::ChannelFetchProgress::from(&progress)).extend();
+            let mut resp = Negotiated(api::ChannelFetchProgress::from(&progress)).extend();
             resp.extensions.insert(ETagCacheControl::DoNotCache);
             Ok(Either::Right((resp, *NOT_READY_YET)))
         },
+        GetChannelOutput::RateLimited(retry_after) => Err(utils::Error::RateLimited(retry_after)),
         GetChannelOutput::Resolved(result) => {
             // we only really need the string pointer's address to figure out if they're equal, thanks to
             // the `StringSet`
             let vid_set: HashSet<usize> = result.video_ids.iter().map(utils::arc_addr).collect();
-            let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+            let db = db_state.load()?;
             let titles = db.db.titles.iter().rev()
                 .filter(|title| vid_set.contains(&utils::arc_addr(&title.video_id)))
                 .map(|t| t.into_with_db(&db.db))
                 .collect();
-            Ok(Either::Left(web::Json(titles)))
+            Ok(Either::Left(Negotiated(titles)))
         }
     }
 }
 
 #[get("/thumbnails", wrap = "ETagCache")]
-async fn get_thumbnails(db_lock: DBLock, query: web::Query<MainEndpointURLParams>) -> JsonResult<Vec<ApiThumbnail>> {
+async fn get_thumbnails(db_state: DBState, query: web::Query<MainEndpointURLParams>) -> ApiResult<Vec<ApiThumbnail>> {
     if query.count > 1024 {
         return Err(
             utils::Error::from(anyhow!("Too many requested thumbnails. You requested {} thumbnails, but the configured max is 1024.", query.count))
                 .set_status(StatusCode::BAD_REQUEST)
         );
     }
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+    let db = db_state.load()?;
+    Ok(Negotiated(
         db.db.thumbnails.iter().rev().skip(query.offset).take(query.count)
             .map(|t| t.into_with_db(&db.db)).collect::<Vec<_>>()
     ))
 }
 
 #[get("/thumbnails/broken", wrap = "ETagCache")]
-async fn get_broken_thumbnails(db_lock: DBLock) -> JsonResult<Vec<ApiThumbnail>> {
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+async fn get_broken_thumbnails(db_state: DBState) -> ApiResult<Vec<ApiThumbnail>> {
+    let db = db_state.load()?;
+    Ok(Negotiated(
         db.db.thumbnails.iter().rev()
             .filter(|t| t.flags.intersects(ThumbnailFlags::MissingVotes | ThumbnailFlags::MissingTimestamp))
             .map(|t| t.into_with_db(&db.db)).collect::<Vec<_>>()
@@ -329,13 +482,12 @@ async fn get_broken_thumbnails(db_lock: DBLock) -> JsonResult<Vec<ApiThumbnail>>
 
 
 #[get("/thumbnails/uuid/{uuid}", wrap = "ETagCache")]
-async fn get_thumbnail_by_uuid(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<ApiThumbnail> {
-    let Some(uuid) = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-            .set.get(path.into_inner().as_str()).cloned() else {
+async fn get_thumbnail_by_uuid(db_state: DBState, path: web::Path<String>) -> ApiResult<ApiThumbnail> {
+    let db = db_state.load()?;
+    let Some(uuid) = db.string_set.set.get(path.into_inner().as_str()).cloned() else {
         return Err(utils::Error::EmptyStatus(StatusCode::NOT_FOUND));
     };
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(
+    Ok(Negotiated(
         db.db.thumbnails.iter().find(|t| Arc::ptr_eq(&t.uuid, &uuid))
             .map(|t| t.into_with_db(&db.db))
             .ok_or(utils::Error::EmptyStatus(StatusCode::NOT_FOUND))?
@@ -343,10 +495,9 @@ async fn get_thumbnail_by_uuid(db_lock: DBLock, string_set: StringSetLock, path:
 }
 
 #[get("/thumbnails/video_id/{video_id}", wrap = "ETagCache")]
-async fn get_thumbnails_by_video_id(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiThumbnail>> {
-    let video_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.into_inner().as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_thumbnails_by_video_id(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiThumbnail>> {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.into_inner().as_str()).cloned();
     let titles = match video_id {
         None => vec![],
         Some(id) => db.db.thumbnails.iter().rev()
@@ -354,14 +505,13 @@ async fn get_thumbnails_by_video_id(db_lock: DBLock, string_set: StringSetLock,
             .map(|t| t.into_with_db(&db.db))
             .collect(),
     };
-    Ok(web::Json(titles))
+    Ok(Negotiated(titles))
 }
 
 #[get("/thumbnails/user_id/{video_id}", wrap = "ETagCache")]
-async fn get_thumbnails_by_user_id(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiThumbnail>> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.into_inner().as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_thumbnails_by_user_id(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiThumbnail>> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.into_inner().as_str()).cloned();
     let titles = match user_id {
         None => vec![],
         Some(id) => db.db.thumbnails.iter().rev()
@@ -369,44 +519,83 @@ async fn get_thumbnails_by_user_id(db_lock: DBLock, string_set: StringSetLock, p
             .map(|t| t.into_with_db(&db.db))
             .collect(),
     };
-    Ok(web::Json(titles))
+    Ok(Negotiated(titles))
 }
 
 #[get("/thumbnails/channel/{channel}", wrap = "ETagCache")]
-async fn get_thumbnails_by_channel(db_lock: DBLock, path: web::Path<String>) -> JsonResultOrFetchProgress<Vec<ApiThumbnail>> {
+async fn get_thumbnails_by_channel(client: ClientIp, db_state: DBState, path: web::Path<String>) -> ApiResultOrFetchProgress<Vec<ApiThumbnail>> {
     let channel_cache = {
-        let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+        let db = db_state.load()?;
         db.channel_cache.clone()
     };
-    let channel_data = channel_cache.get_channel(path.into_inner().as_str()).await.context("Failed to get channel info")?;
+    let channel_data = channel_cache.get_channel(path.into_inner().as_str(), client).await.context("Failed to get channel info")?;
 
     match channel_data {
         GetChannelOutput::Pending(progress) => {
-            let mut resp = web::Json(api::ChannelFetchProgress::from(&progress)).extend();
+            let mut resp = Negotiated(api::ChannelFetchProgress::from(&progress)).extend();
             resp.extensions.insert(ETagCacheControl::DoNotCache);
             Ok(Either::Right((resp, *NOT_READY_YET)))
         },
+        GetChannelOutput::RateLimited(retry_after) => Err(utils::Error::RateLimited(retry_after)),
         GetChannelOutput::Resolved(result) => {
             // we only really need the string pointer's address to figure out if they're equal, thanks to
             // the `StringSet`
             let vid_set: HashSet<usize> = result.video_ids.iter().map(utils::arc_addr).collect();
-            let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+            let db = db_state.load()?;
             let thumbs = db.db.thumbnails.iter().rev()
                 .filter(|thumbnail| vid_set.contains(&utils::arc_addr(&thumbnail.video_id)))
                 .map(|t| t.into_with_db(&db.db))
                 .collect();
-            Ok(Either::Left(web::Json(thumbs)))
+            Ok(Either::Left(Negotiated(thumbs)))
         }
     }
 
 }
 
+/// Builds `User` summaries for all given user IDs in a single pass over the database
+///
+/// All user IDs must be deduplicated using the `StringSet`.
+fn summarize_users(db: &DearrowDB, user_ids: &[Arc<str>]) -> Vec<User> {
+    let mut users: Vec<User> = user_ids.iter().map(|user_id| {
+        let username = db.usernames.get(user_id);
+        User {
+            user_id: user_id.clone(),
+            username: username.map(|u| u.username.clone()),
+            username_locked: username.is_some_and(|u| u.locked),
+            vip: db.vip_users.contains(user_id),
+            title_count: 0,
+            thumbnail_count: 0,
+            warning_count: 0,
+            active_warning_count: 0,
+        }
+    }).collect();
+    // we only really need the string pointer's address to figure out if they're equal, thanks to
+    // the `StringSet`
+    let indexes: HashMap<usize, usize> = user_ids.iter().enumerate().map(|(i, id)| (utils::arc_addr(id), i)).collect();
+    for title in &db.titles {
+        if let Some(&i) = indexes.get(&utils::arc_addr(&title.user_id)) {
+            users[i].title_count += 1;
+        }
+    }
+    for thumb in &db.thumbnails {
+        if let Some(&i) = indexes.get(&utils::arc_addr(&thumb.user_id)) {
+            users[i].thumbnail_count += 1;
+        }
+    }
+    for warning in &db.warnings {
+        if let Some(&i) = indexes.get(&utils::arc_addr(&warning.warned_user_id)) {
+            users[i].warning_count += 1;
+            users[i].active_warning_count += u64::from(warning.active);
+        }
+    }
+    users
+}
+
 #[get("/users/user_id/{user_id}", wrap = "ETagCache")]
-async fn get_user_by_userid(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<User> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(match user_id {
+async fn get_user_by_userid(db_state: DBState, path: web::Path<String>) -> ApiResult<User> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.as_str()).cloned();
+    Ok(Negotiated(match user_id {
         None => User {
             user_id: path.into_inner().into(),
             username: None, 
@@ -417,53 +606,252 @@ async fn get_user_by_userid(db_lock: DBLock, string_set: StringSetLock, path: we
             warning_count: 0,
             active_warning_count: 0,
         },
-        Some(user_id) => {
-            let username = db.db.usernames.get(&user_id);
-            let (warning_count, active_warnings) = db.db.warnings.iter().fold((0,0), |acc, w| {
-                if w.warned_user_id != user_id {
-                    acc
-                } else if w.active {
-                    (acc.0+1, acc.1+1)
-                } else {
-                    (acc.0+1, acc.1)
-                }
-            });
-            User {
-                user_id: user_id.clone(),
-                username: username.map(|u| u.username.clone()),
-                username_locked: username.map_or(false, |u| u.locked),
-                vip: db.db.vip_users.contains(&user_id),
-                title_count: db.db.titles.iter().filter(|t| Arc::ptr_eq(&t.user_id, &user_id)).count() as u64,
-                thumbnail_count: db.db.thumbnails.iter().filter(|t| Arc::ptr_eq(&t.user_id, &user_id)).count() as u64,
-                warning_count,
-                active_warning_count: active_warnings,
-            }
-        }
+        Some(user_id) => summarize_users(&db.db, &[user_id]).pop().expect("summarize_users should return one User per user ID"),
     }))
 }
 
+#[derive(Deserialize)]
+struct UserSearchURLParams {
+    query: String,
+    #[serde(default = "default_user_search_count")]
+    count: usize,
+}
+
+fn default_user_search_count() -> usize {
+    20
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum UserMatchKind {
+    ExactUsername,
+    UserIDPrefix,
+    UsernameSubstring,
+}
+
+#[get("/users/search", wrap = "ETagCache")]
+async fn search_users(db_state: DBState, query: web::Query<UserSearchURLParams>) -> ApiResult<UserSearchResult> {
+    if query.count > USER_SEARCH_MAX_COUNT {
+        return Err(
+            utils::Error::from(anyhow!("Too many requested users. You requested {} users, but the configured max is {USER_SEARCH_MAX_COUNT}.", query.count))
+                .set_status(StatusCode::BAD_REQUEST)
+        );
+    }
+    let search = query.query.trim().to_lowercase();
+    if search.chars().count() < USER_SEARCH_MIN_LENGTH {
+        return Err(
+            utils::Error::from(anyhow!("Search query too short. Queries must be at least {} characters long.", USER_SEARCH_MIN_LENGTH))
+                .set_status(StatusCode::BAD_REQUEST)
+        );
+    }
+    let db = db_state.load()?;
+    let count = query.count;
+    // the search scans every submission, so it's kept off the async workers
+    let result = web::block(move || find_users(&db, &search, count)).await.context("Failed to search for users")?;
+    Ok(Negotiated(result))
+}
+
+fn find_users(db: &DatabaseState, search: &str, count: usize) -> UserSearchResult {
+    // keyed by the address of the user ID string, thanks to the `StringSet`
+    let mut matches: HashMap<usize, (UserMatchKind, Arc<str>)> = HashMap::new();
+    let mut add_match = |kind: UserMatchKind, user_id: &Arc<str>| {
+        matches.entry(utils::arc_addr(user_id))
+            .and_modify(|m| m.0 = m.0.min(kind))
+            .or_insert_with(|| (kind, user_id.clone()));
+    };
+    for username in db.db.usernames.values() {
+        let lowercase = username.username.to_lowercase();
+        if lowercase == search {
+            add_match(UserMatchKind::ExactUsername, &username.user_id);
+        } else if lowercase.contains(search) {
+            add_match(UserMatchKind::UsernameSubstring, &username.user_id);
+        }
+        if username.user_id.starts_with(search) {
+            add_match(UserMatchKind::UserIDPrefix, &username.user_id);
+        }
+    }
+    db.db.titles.iter().map(|t| &t.user_id)
+        .chain(db.db.thumbnails.iter().map(|t| &t.user_id))
+        .filter(|user_id| user_id.starts_with(search))
+        .for_each(|user_id| add_match(UserMatchKind::UserIDPrefix, user_id));
+
+    let (kinds, user_ids): (Vec<UserMatchKind>, Vec<Arc<str>>) = matches.into_values().unzip();
+    let mut users: Vec<(UserMatchKind, User)> = kinds.into_iter().zip(summarize_users(&db.db, &user_ids)).collect();
+    users.sort_unstable_by(|(a_kind, a), (b_kind, b)|
+        a_kind.cmp(b_kind)
+            .then_with(|| (b.title_count + b.thumbnail_count).cmp(&(a.title_count + a.thumbnail_count)))
+            .then_with(|| a.user_id.cmp(&b.user_id))
+    );
+    let truncated = users.len() > count;
+    users.truncate(count);
+
+    let mut username_counts: HashMap<Arc<str>, usize> = users.iter()
+        .filter_map(|(_, u)| u.username.clone())
+        .map(|name| (name, 0))
+        .collect();
+    for username in db.db.usernames.values() {
+        if let Some(count) = username_counts.get_mut(&username.username) {
+            *count += 1;
+        }
+    }
+    let mut username_collisions: Vec<Arc<str>> = username_counts.into_iter().filter(|(_, count)| *count > 1).map(|(name, _)| name).collect();
+    username_collisions.sort_unstable();
+
+    UserSearchResult {
+        users: users.into_iter().map(|(_, u)| u).collect(),
+        username_collisions,
+        truncated,
+    }
+}
+
 #[get("/warnings/user_id/{user_id}/received")]
-async fn get_user_warnings(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiWarning>> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(match user_id {
+async fn get_user_warnings(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiWarning>> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.as_str()).cloned();
+    Ok(Negotiated(match user_id {
         None => vec![],
         Some(user_id) => db.db.warnings.iter().rev().filter(|w| Arc::ptr_eq(&w.warned_user_id, &user_id)).map(ApiWarning::from).collect(),
     }))
 }
 
 #[get("/warnings/user_id/{user_id}/issued")]
-async fn get_issued_warnings(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Vec<ApiWarning>> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
-    Ok(web::Json(match user_id {
+async fn get_issued_warnings(db_state: DBState, path: web::Path<String>) -> ApiResult<Vec<ApiWarning>> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(path.as_str()).cloned();
+    Ok(Negotiated(match user_id {
         None => vec![],
         Some(user_id) => db.db.warnings.iter().rev().filter(|w| Arc::ptr_eq(&w.issuer_user_id, &user_id)).map(ApiWarning::from).collect(),
     }))
 }
 
+#[derive(Deserialize)]
+#[serde(default)]
+struct LeaderboardURLParams {
+    count: usize,
+}
+
+impl Default for LeaderboardURLParams {
+    fn default() -> Self {
+        Self {
+            count: 25,
+        }
+    }
+}
+
+struct UserStats {
+    user_id: Arc<str>,
+    title_count: u64,
+    thumbnail_count: u64,
+    locked_count: u64,
+    score_sum: i64,
+    recent_count: u64,
+}
+
+impl UserStats {
+    fn new(user_id: &Arc<str>) -> UserStats {
+        UserStats {
+            user_id: user_id.clone(),
+            title_count: 0,
+            thumbnail_count: 0,
+            locked_count: 0,
+            score_sum: 0,
+            recent_count: 0,
+        }
+    }
+
+    fn submission_count(&self) -> u64 {
+        self.title_count + self.thumbnail_count
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn average_score(&self) -> f64 {
+        match self.submission_count() {
+            0 => 0.,
+            count => self.score_sum as f64 / count as f64,
+        }
+    }
+
+    fn to_entry(&self, db: &DearrowDB) -> LeaderboardEntry {
+        LeaderboardEntry {
+            user_id: self.user_id.clone(),
+            username: db.usernames.get(&self.user_id).map(|u| u.username.clone()),
+            vip: db.vip_users.contains(&self.user_id),
+            title_count: self.title_count,
+            thumbnail_count: self.thumbnail_count,
+            locked_count: self.locked_count,
+            average_score: self.average_score(),
+            recent_count: self.recent_count,
+        }
+    }
+}
+
+/// Picks the `count` highest ranked users, ordered by `compare` in descending order
+fn top_users<'a>(stats: &[&'a UserStats], count: usize, filter: impl Fn(&UserStats) -> bool, compare: impl Fn(&UserStats, &UserStats) -> Ordering) -> Vec<&'a UserStats> {
+    let mut list: Vec<&UserStats> = stats.iter().copied().filter(|s| filter(s)).collect();
+    list.sort_unstable_by(|a, b| compare(b, a).then_with(|| a.user_id.cmp(&b.user_id)));
+    list.truncate(count);
+    list
+}
+
+/// Ranks all users, keeping the top [`LEADERBOARD_MAX_COUNT`] entries of every leaderboard
+fn compute_leaderboards(db: &DatabaseState) -> Leaderboards {
+    // relative to the reload, so that the leaderboards don't change within a snapshot
+    let recent_cutoff = db.last_updated - LEADERBOARD_RECENT_WINDOW_MS;
+
+    // keyed by the address of the user ID string, thanks to the `StringSet`
+    let mut stats: HashMap<usize, UserStats> = HashMap::new();
+    for title in &db.db.titles {
+        let entry = stats.entry(utils::arc_addr(&title.user_id)).or_insert_with(|| UserStats::new(&title.user_id));
+        entry.title_count += 1;
+        entry.locked_count += u64::from(title.flags.contains(TitleFlags::Locked));
+        entry.score_sum += i64::from(title.votes) - i64::from(title.downvotes) - i64::from(title.flags.contains(TitleFlags::Unverified));
+        entry.recent_count += u64::from(title.time_submitted >= recent_cutoff);
+    }
+    for thumb in &db.db.thumbnails {
+        let entry = stats.entry(utils::arc_addr(&thumb.user_id)).or_insert_with(|| UserStats::new(&thumb.user_id));
+        entry.thumbnail_count += 1;
+        entry.locked_count += u64::from(thumb.flags.contains(ThumbnailFlags::Locked));
+        entry.score_sum += i64::from(thumb.votes) - i64::from(thumb.downvotes);
+        entry.recent_count += u64::from(thumb.time_submitted >= recent_cutoff);
+    }
+    let stats: Vec<&UserStats> = stats.values().collect();
+    let count = LEADERBOARD_MAX_COUNT;
+    let to_entries = |list: Vec<&UserStats>| list.into_iter().map(|s| s.to_entry(&db.db)).collect::<Vec<_>>();
+
+    Leaderboards {
+        titles: to_entries(top_users(&stats, count, |s| s.title_count > 0, |a, b| a.title_count.cmp(&b.title_count))),
+        thumbnails: to_entries(top_users(&stats, count, |s| s.thumbnail_count > 0, |a, b| a.thumbnail_count.cmp(&b.thumbnail_count))),
+        locked: to_entries(top_users(&stats, count, |s| s.locked_count > 0, |a, b| a.locked_count.cmp(&b.locked_count))),
+        average_score: to_entries(top_users(&stats, count, |s| s.submission_count() >= LEADERBOARD_AVERAGE_MIN_SUBMISSIONS, |a, b| a.average_score().total_cmp(&b.average_score()))),
+        recently_active: to_entries(top_users(&stats, count, |s| s.recent_count > 0, |a, b| a.recent_count.cmp(&b.recent_count))),
+    }
+}
+
+#[get("/leaderboard", wrap = "ETagCache")]
+async fn get_leaderboard(db_state: DBState, query: web::Query<LeaderboardURLParams>) -> ApiResult<Leaderboards> {
+    if query.count > LEADERBOARD_MAX_COUNT {
+        return Err(
+            utils::Error::from(anyhow!("Too many requested leaderboard entries. You requested {} entries, but the configured max is {LEADERBOARD_MAX_COUNT}.", query.count))
+                .set_status(StatusCode::BAD_REQUEST)
+        );
+    }
+    let db = db_state.load()?;
+    // computed by the first request for every snapshot, the rest only pick the top entries.
+    // ranking scans every submission, so it's kept off the async workers
+    if db.leaderboards.get().is_none() {
+        let db = db.clone();
+        web::block(move || { db.leaderboards.get_or_init(|| compute_leaderboards(&db)); }).await.context("Failed to compute the leaderboards")?;
+    }
+    let leaderboards = db.leaderboards.get().expect("The leaderboards should be computed by now");
+    let top = |list: &[LeaderboardEntry]| list[..list.len().min(query.count)].to_vec();
+    Ok(Negotiated(Leaderboards {
+        titles: top(&leaderboards.titles),
+        thumbnails: top(&leaderboards.thumbnails),
+        locked: top(&leaderboards.locked),
+        average_score: top(&leaderboards.average_score),
+        recently_active: top(&leaderboards.recently_active),
+    }))
+}
+
 
 fn unknown_video(video_id: Arc<str>) -> Video {
     Video { 
@@ -476,13 +864,12 @@ fn unknown_video(video_id: Arc<str>) -> Video {
 }
 
 #[get("/videos/{video_id}", wrap = "ETagCache")]
-async fn get_video(db_lock: DBLock, string_set: StringSetLock, path: web::Path<String>) -> JsonResult<Video> {
-    let video_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(path.as_str()).cloned();
-    Ok(web::Json(match video_id {
+async fn get_video(db_state: DBState, path: web::Path<String>) -> ApiResult<Video> {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.as_str()).cloned();
+    Ok(Negotiated(match video_id {
         None => unknown_video(path.as_str().into()),
         Some(video_id) => {
-            let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
             let video_info = db.db.get_video_info(&video_id);
             match video_info {
                 None => unknown_video(video_id),
@@ -497,3 +884,60 @@ async fn get_video(db_lock: DBLock, string_set: StringSetLock, path: web::Path<S
         },
     }))
 }
+
+/// Marks all displayable submissions after the first one as outranked, then appends the hidden ones
+fn rank_branding<T, K: Ord>(
+    submissions: Vec<(T, Option<BrandingHiddenReason>)>,
+    rank: impl Fn(&T) -> K,
+) -> Vec<(T, Option<BrandingHiddenReason>)> {
+    let (mut shown, hidden): (Vec<_>, Vec<_>) = submissions.into_iter().partition(|(_, reason)| reason.is_none());
+    shown.sort_by_key(|(s, _)| std::cmp::Reverse(rank(s)));
+    for (_, reason) in shown.iter_mut().skip(1) {
+        *reason = Some(BrandingHiddenReason::Outranked);
+    }
+    shown.extend(hidden);
+    shown
+}
+
+#[get("/videos/{video_id}/branding", wrap = "ETagCache")]
+async fn get_video_branding(db_state: DBState, path: web::Path<String>) -> ApiResult<VideoBranding> {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(path.as_str()).cloned();
+    Ok(Negotiated(match video_id {
+        None => VideoBranding {
+            random_time: get_random_time_for_video(path.as_str(), None),
+            video_id: path.into_inner().into(),
+            titles: vec![],
+            thumbnails: vec![],
+            video_duration: None,
+        },
+        Some(video_id) => {
+            let video_info = db.db.get_video_info(&video_id);
+            let titles = rank_branding(
+                db.db.titles.iter().rev()
+                    .filter(|t| Arc::ptr_eq(&t.video_id, &video_id))
+                    .map(|t| (t, title_hidden_reason(t, false)))
+                    .collect(),
+                |t| title_rank(t),
+            );
+            let thumbnails = rank_branding(
+                db.db.thumbnails.iter().rev()
+                    .filter(|t| Arc::ptr_eq(&t.video_id, &video_id))
+                    .map(|t| (t, thumbnail_hidden_reason(t, false)))
+                    .collect(),
+                |t| thumbnail_rank(t),
+            );
+            VideoBranding {
+                titles: titles.into_iter()
+                    .map(|(t, hidden_reason)| BrandingTitle { title: t.into_with_db(&db.db), hidden_reason })
+                    .collect(),
+                thumbnails: thumbnails.into_iter()
+                    .map(|(t, hidden_reason)| BrandingThumbnail { thumbnail: t.into_with_db(&db.db), hidden_reason })
+                    .collect(),
+                random_time: get_random_time_for_video(&video_id, video_info),
+                video_duration: video_info.map(|i| i.video_duration),
+                video_id,
+            }
+        },
+    }))
+}
@@ -19,16 +19,16 @@
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 #![allow(clippy::needless_pass_by_value)]
-use std::{sync::Arc, collections::HashMap};
+use std::{cmp::Reverse, collections::HashMap, sync::{atomic::Ordering::Relaxed, Arc}};
 
 use actix_web::{get, http::StatusCode, post, web, CustomizeResponder, HttpResponse, Responder};
 use alea_js::Alea;
 use error_handling::anyhow;
-use dearrow_parser::{Extension, Thumbnail, ThumbnailFlags, Title, TitleFlags, VideoInfo};
+use dearrow_browser_api::sync::BrandingHiddenReason;
+use dearrow_parser::{DearrowDB, Extension, Thumbnail, ThumbnailFlags, Title, TitleFlags, VideoInfo};
 use serde::{Deserialize, Serialize};
 
-use crate::{middleware::ETagCache, state::{DBLock, StringSetLock}, utils};
-use crate::constants::*;
+use crate::{metrics::METRICS, middleware::ETagCache, state::DBState, utils};
 
 type JsonResult<T> = utils::Result<web::Json<T>>;
 type CustomizedJsonResult<T> = utils::Result<CustomizeResponder<web::Json<T>>>;
@@ -157,6 +157,79 @@ pub fn get_random_time_for_video(video_id: &str, video_info: Option<&VideoInfo>)
     }
 }
 
+/// Decides whether a title would be returned by the `SponsorBlock` server's branding endpoints
+///
+/// Returns `None` if the title would be included in the response.
+// https://github.com/ajayyy/SponsorBlockServer/blob/af31f511a53a7e30ad27123656a911393200672b/src/routes/getBranding.ts#L58
+pub fn title_hidden_reason(title: &Title, fetch_all: bool) -> Option<BrandingHiddenReason> {
+    let score = title.votes.saturating_sub(title.downvotes);
+    let unverified = title.flags.contains(TitleFlags::Unverified);
+    if title.flags.contains(TitleFlags::Removed) {
+        Some(BrandingHiddenReason::Removed)
+    } else if title.flags.contains(TitleFlags::ShadowHidden) {
+        Some(BrandingHiddenReason::ShadowHidden)
+    } else if title.flags.contains(TitleFlags::MissingVotes) {
+        Some(BrandingHiddenReason::MissingVotes)
+    } else if title.votes <= -1 || score <= -2 {
+        Some(BrandingHiddenReason::BelowVoteThreshold)
+    } else if fetch_all || title.flags.contains(TitleFlags::Locked) || score >= unverified.into() {
+        None
+    } else if unverified && score >= 0 {
+        Some(BrandingHiddenReason::UnverifiedNotLocked)
+    } else {
+        Some(BrandingHiddenReason::BelowVoteThreshold)
+    }
+}
+
+/// Decides whether a thumbnail would be returned by the `SponsorBlock` server's branding endpoints
+///
+/// Returns `None` if the thumbnail would be included in the response.
+pub fn thumbnail_hidden_reason(thumb: &Thumbnail, fetch_all: bool) -> Option<BrandingHiddenReason> {
+    let score = thumb.votes.saturating_sub(thumb.downvotes);
+    let original = thumb.flags.contains(ThumbnailFlags::Original);
+    if thumb.flags.contains(ThumbnailFlags::Removed) {
+        Some(BrandingHiddenReason::Removed)
+    } else if thumb.flags.contains(ThumbnailFlags::ShadowHidden) {
+        Some(BrandingHiddenReason::ShadowHidden)
+    } else if thumb.flags.contains(ThumbnailFlags::MissingVotes) {
+        Some(BrandingHiddenReason::MissingVotes)
+    } else if thumb.flags.contains(ThumbnailFlags::MissingTimestamp) {
+        Some(BrandingHiddenReason::MissingTimestamp)
+    } else if score <= -2 {
+        Some(BrandingHiddenReason::BelowVoteThreshold)
+    } else if (fetch_all && !original) || thumb.flags.contains(ThumbnailFlags::Locked) || score >= original.into() {
+        None
+    } else {
+        Some(BrandingHiddenReason::BelowVoteThreshold)
+    }
+}
+
+/// Sort key for titles returned by the branding endpoint, the highest key is displayed
+pub fn title_rank(title: &Title) -> (bool, i8) {
+    (
+        title.flags.contains(TitleFlags::Locked),
+        title.votes.saturating_sub(title.downvotes).saturating_sub(title.flags.contains(TitleFlags::Unverified).into()),
+    )
+}
+
+/// The highest ranked title of the video that the branding endpoint would return.
+///
+/// The endpoint leaves the order of tied titles unspecified, here ties go to the newest title.
+pub fn top_title<'a>(db: &'a DearrowDB, video_id: &Arc<str>) -> Option<&'a Title> {
+    db.titles.iter()
+        .filter(|t| Arc::ptr_eq(&t.video_id, video_id) && title_hidden_reason(t, false).is_none())
+        .max_by_key(|t| (title_rank(t), t.time_submitted))
+}
+
+/// Sort key for thumbnails returned by the branding endpoint, the highest key is displayed
+pub fn thumbnail_rank(thumb: &Thumbnail) -> (bool, i8, bool) {
+    (
+        thumb.flags.contains(ThumbnailFlags::Locked),
+        thumb.votes.saturating_sub(thumb.downvotes),
+        !thumb.flags.contains(ThumbnailFlags::Original),
+    )
+}
+
 fn unknown_video(video_id: &str) -> SBApiVideo {
     SBApiVideo {
         titles: vec![],
@@ -172,10 +245,9 @@ async fn post_video_branding() -> HttpResponse {
 }
 
 #[get("/api/branding", wrap = "ETagCache")]
-async fn get_video_branding(db_lock: DBLock, string_set: StringSetLock, query: web::Query<VideoBrandingParams>) -> CustomizedJsonResult<SBApiVideo> {
-    let video_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(query.0.videoID.as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_video_branding(db_state: DBState, query: web::Query<VideoBrandingParams>) -> CustomizedJsonResult<SBApiVideo> {
+    let db = db_state.load()?;
+    let video_id = db.string_set.set.get(query.0.videoID.as_str()).cloned();
     if let Some(service) = query.0.service {
         if service != "YouTube" {
             return Ok(web::Json(unknown_video(&query.0.videoID)).customize().with_status(StatusCode::NOT_FOUND));
@@ -187,39 +259,20 @@ async fn get_video_branding(db_lock: DBLock, string_set: StringSetLock, query: w
             let video_info = db.db.get_video_info(&video_id);
             Ok(web::Json(SBApiVideo {
                 titles: {
-                    let mut titles: Vec<SBApiTitle> = db.db.titles.iter()
-                        .filter(|t| 
-                            Arc::ptr_eq(&t.video_id, &video_id) 
-                            && t.votes > -1 
-                            && t.votes.saturating_sub(t.downvotes) > -2 
-                            && !t.flags.intersects(TitleFlags::Removed | TitleFlags::ShadowHidden | TitleFlags::MissingVotes)
-                            && (
-                                query.0.fetchAll 
-                                || t.flags.contains(TitleFlags::Locked) 
-                                || t.votes.saturating_sub(t.downvotes) >= t.flags.contains(TitleFlags::Unverified).into()
-                            )
-                        )
-                        .map(|t| SBApiTitle::from_db(t, query.0.returnUserID))
+                    let mut titles: Vec<&Title> = db.db.titles.iter()
+                        .filter(|t| Arc::ptr_eq(&t.video_id, &video_id) && title_hidden_reason(t, query.0.fetchAll).is_none())
                         .collect();
-                    titles.sort_unstable_by(|a, b| a.locked.cmp(&b.locked).then(a.votes.cmp(&b.votes)).reverse());
-                    titles
+                    titles.sort_unstable_by_key(|t| Reverse(title_rank(t)));
+                    METRICS.sbserver_titles_served.fetch_add(titles.len() as u64, Relaxed);
+                    titles.into_iter().map(|t| SBApiTitle::from_db(t, query.0.returnUserID)).collect()
                 },
                 thumbnails: {
-                    let mut thumbs: Vec<SBApiThumbnail> = db.db.thumbnails.iter()
-                        .filter(|t|
-                            Arc::ptr_eq(&t.video_id, &video_id) 
-                            && t.votes.saturating_sub(t.downvotes) > -2 
-                            && !t.flags.intersects(ThumbnailFlags::Removed | ThumbnailFlags::ShadowHidden | ThumbnailFlags::MissingVotes | ThumbnailFlags::MissingTimestamp)
-                            && (
-                                (query.0.fetchAll && !t.flags.contains(ThumbnailFlags::Original))
-                                || t.flags.contains(ThumbnailFlags::Locked)
-                                || t.votes.saturating_sub(t.downvotes) >= t.flags.contains(ThumbnailFlags::Original).into()
-                            )
-                        )
-                        .map(|t| SBApiThumbnail::from_db(t, query.0.returnUserID))
+                    let mut thumbs: Vec<&Thumbnail> = db.db.thumbnails.iter()
+                        .filter(|t| Arc::ptr_eq(&t.video_id, &video_id) && thumbnail_hidden_reason(t, query.0.fetchAll).is_none())
                         .collect();
-                    thumbs.sort_unstable_by(|a, b| a.locked.cmp(&b.locked).then(a.votes.cmp(&b.votes).then(a.original.cmp(&b.original).reverse())).reverse());
-                    thumbs
+                    thumbs.sort_unstable_by_key(|t| Reverse(thumbnail_rank(t)));
+                    METRICS.sbserver_thumbnails_served.fetch_add(thumbs.len() as u64, Relaxed);
+                    thumbs.into_iter().map(|t| SBApiThumbnail::from_db(t, query.0.returnUserID)).collect()
                 },
                 randomTime: get_random_time_for_video(&video_id, video_info),
                 videoDuration: video_info.map(|v| v.video_duration),
@@ -245,8 +298,8 @@ struct ChunkBrandingPath {
 }
 
 #[get("/api/branding/{hash_prefix}", wrap = "ETagCache")]
-async fn get_chunk_branding(db_lock: DBLock, query: web::Query<ChunkBrandingParams>, path: web::Path<ChunkBrandingPath>) -> CustomizedJsonResult<HashMap<Arc<str>, SBApiVideo>> {
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_chunk_branding(db_state: DBState, query: web::Query<ChunkBrandingParams>, path: web::Path<ChunkBrandingPath>) -> CustomizedJsonResult<HashMap<Arc<str>, SBApiVideo>> {
+    let db = db_state.load()?;
     if let Some(service) = query.0.service {
         if service != "YouTube" {
             return Ok(web::Json(HashMap::new()).customize().with_status(StatusCode::NOT_FOUND));
@@ -262,17 +315,7 @@ async fn get_chunk_branding(db_lock: DBLock, query: web::Query<ChunkBrandingPara
     let mut videos: HashMap<Arc<str>, Option<&VideoInfo>> = db.db.video_infos[hash_prefix as usize].iter().map(|v| (v.video_id.clone(), Some(v))).collect();
     let mut titles: HashMap<Arc<str>, Vec<SBApiTitle>> = HashMap::new();
     db.db.titles.iter()
-        .filter(|t|
-            t.hash_prefix == hash_prefix
-            && t.votes > -1 
-            && !t.flags.intersects(TitleFlags::Removed | TitleFlags::ShadowHidden | TitleFlags::MissingVotes)
-            && t.votes.saturating_sub(t.downvotes) > -2 
-            && (
-                query.0.fetchAll 
-                || t.flags.contains(TitleFlags::Locked) 
-                || t.votes.saturating_sub(t.downvotes) >= t.flags.contains(TitleFlags::Unverified).into()
-            )
-        )
+        .filter(|t| t.hash_prefix == hash_prefix && title_hidden_reason(t, query.0.fetchAll).is_none())
         .for_each(|t| match titles.get_mut(&t.video_id) {
             Some(v) => v.push(SBApiTitle::from_db(t, query.0.returnUserID)),
             None => {
@@ -282,16 +325,7 @@ async fn get_chunk_branding(db_lock: DBLock, query: web::Query<ChunkBrandingPara
         });
     let mut thumbnails: HashMap<Arc<str>, Vec<SBApiThumbnail>> = HashMap::new();
     db.db.thumbnails.iter()
-        .filter(|t|
-            t.hash_prefix == hash_prefix
-            && !t.flags.intersects(ThumbnailFlags::Removed | ThumbnailFlags::ShadowHidden | ThumbnailFlags::MissingVotes | ThumbnailFlags::MissingTimestamp)
-            && t.votes.saturating_sub(t.downvotes) > -2 
-            && (
-                (query.0.fetchAll && !t.flags.contains(ThumbnailFlags::Original))
-                || t.flags.contains(ThumbnailFlags::Locked)
-                || t.votes.saturating_sub(t.downvotes) >= t.flags.contains(ThumbnailFlags::Original).into()
-            )
-        )
+        .filter(|t| t.hash_prefix == hash_prefix && thumbnail_hidden_reason(t, query.0.fetchAll).is_none())
         .for_each(|t| match thumbnails.get_mut(&t.video_id) {
             Some(v) => v.push(SBApiThumbnail::from_db(t, query.0.returnUserID)),
             None => {
@@ -300,6 +334,9 @@ async fn get_chunk_branding(db_lock: DBLock, query: web::Query<ChunkBrandingPara
             },
         });
 
+    METRICS.sbserver_titles_served.fetch_add(titles.values().map(|t| t.len() as u64).sum(), Relaxed);
+    METRICS.sbserver_thumbnails_served.fetch_add(thumbnails.values().map(|t| t.len() as u64).sum(), Relaxed);
+
     // Construct response
     Ok(web::Json(videos.into_iter().map(|(v, info)| (v.clone(), SBApiVideo {
         titles: titles.get(&v).cloned().unwrap_or_default(),
@@ -329,10 +366,9 @@ struct UserInfo {
 }
 
 #[get("/api/userInfo", wrap = "ETagCache")]
-async fn get_user_info(db_lock: DBLock, string_set: StringSetLock, query: web::Query<UserInfoParams>) -> JsonResult<UserInfo> {
-    let user_id = string_set.read().map_err(|_| SS_READ_ERR.clone())?
-        .set.get(query.0.publicUserID.as_str()).cloned();
-    let db = db_lock.read().map_err(|_| DB_READ_ERR.clone())?;
+async fn get_user_info(db_state: DBState, query: web::Query<UserInfoParams>) -> JsonResult<UserInfo> {
+    let db = db_state.load()?;
+    let user_id = db.string_set.set.get(query.0.publicUserID.as_str()).cloned();
     Ok(web::Json(match user_id {
         None => {
             let user_id: Arc<str> = query.0.publicUserID.into();
@@ -0,0 +1,144 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*  
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{fs::{set_permissions, Permissions}, os::unix::prelude::PermissionsExt, path::PathBuf, sync::Arc};
+
+use actix_web::{middleware::{Compress, NormalizePath}, rt::{self, signal::unix::{signal, SignalKind}}, App, HttpServer};
+use error_handling::{ErrorContext, ResContext};
+use futures::{future::try_join, select_biased, FutureExt};
+use log::{info, warn};
+
+use crate::state::AppConfig;
+use crate::systemd::{self, InheritedListener};
+use crate::tls;
+use crate::DeArrowBrowser;
+
+/// Runs the standalone server until it's shut down
+pub async fn serve(config: AppConfig, config_path: PathBuf) -> Result<(), ErrorContext> {
+    let browser = DeArrowBrowser::builder(config).config_path(config_path).build()?;
+    // settings that can't change at runtime are taken from the startup config
+    let config = browser.config();
+    let tls = config.listen.tls.as_ref().map(tls::CertResolver::new).transpose().context("Failed to load the TLS certificate")?.map(Arc::new);
+    {
+        let browser = browser.clone();
+        let tls = tls.clone();
+        let mut hangups = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+        rt::spawn(async move {
+            while hangups.recv().await.is_some() {
+                info!("Received SIGHUP, reloading the config");
+                if let Err(e) = browser.reload_config() {
+                    warn!("Failed to reload the config: {e:?}");
+                }
+                if let Some(ref tls) = tls {
+                    match tls.reload() {
+                        Ok(()) => info!("Reloaded the TLS certificate"),
+                        Err(e) => warn!("Failed to reload the TLS certificate, keeping the old one: {e:?}"),
+                    }
+                }
+            }
+        });
+    }
+    {
+        let browser = browser.clone();
+        let mut terminations = signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+        let mut interrupts = signal(SignalKind::interrupt()).context("Failed to listen for SIGINT")?;
+        // the servers handle SIGTERM and SIGINT themselves, by waiting for in-flight requests to finish,
+        // but feed streams never finish on their own
+        rt::spawn(async move {
+            let name = select_biased! {
+                signal = terminations.recv().fuse() => signal.map(|()| "SIGTERM"),
+                signal = interrupts.recv().fuse() => signal.map(|()| "SIGINT"),
+            };
+            if let Some(name) = name {
+                info!("Received {name}, finishing in-flight requests");
+                browser.close_feeds();
+            }
+        });
+    }
+
+    let metrics_server = match config.metrics.listen {
+        Some((ref ip, port)) if config.metrics.enable => {
+            let browser = browser.clone();
+            let ip_str = ip.as_str();
+            let server = HttpServer::new(move || {
+                App::new().configure(|cfg| browser.configure_metrics(cfg))
+            })
+            .workers(1)
+            .shutdown_timeout(config.shutdown_timeout_secs)
+            .bind((ip_str, port)).with_context(|| format!("Failed to bind the metrics server to tcp port {ip_str}:{port}"))?;
+            info!("Serving metrics on {ip_str}:{port}");
+            Some(server.run())
+        },
+        _ => None,
+    };
+
+    let mut server = {
+        let browser = browser.clone();
+        HttpServer::new(move || {
+            App::new()
+                .wrap(NormalizePath::trim())
+                .wrap(Compress::default())
+                .configure(|cfg| browser.configure(cfg))
+        })
+        .shutdown_timeout(config.shutdown_timeout_secs)
+    };
+    if let Some((ref ip, port)) = config.listen.tcp {
+        let ip_str = ip.as_str();
+        if let Some(ref tls) = tls {
+            server = server.bind_rustls_0_23((ip_str, port), tls.clone().server_config()?).with_context(|| format!("Failed to bind to tcp port {ip_str}:{port}"))?;
+            info!("Listening on https://{ip_str}:{port}");
+        } else {
+            server = server.bind((ip_str, port)).with_context(|| format!("Failed to bind to tcp port {ip_str}:{port}"))?;
+            info!("Listening on {ip_str}:{port}");
+        }
+    }
+    if let Some(ref path) = config.listen.unix {
+        let path_str = path.as_str();
+        server = server.bind_uds(path_str).with_context(|| format!("Failed to bind to unix socket {path_str}"))?;
+        if let Some(mode) = config.listen.unix_mode {
+            let perms = Permissions::from_mode(mode);
+            set_permissions(path_str, perms).with_context(|| format!("Failed to change mode of unix socket {path_str} to {mode}"))?;
+        }
+        info!("Listening on {path_str}");
+    }
+    for listener in systemd::take_listeners().context("Failed to take over the sockets passed by systemd")? {
+        match listener {
+            InheritedListener::Tcp(listener) => {
+                let addr = listener.local_addr().context("Failed to get the address of an inherited tcp socket")?;
+                if let Some(ref tls) = tls {
+                    server = server.listen_rustls_0_23(listener, tls.clone().server_config()?).with_context(|| format!("Failed to listen on inherited tcp socket {addr}"))?;
+                    info!("Listening on https://{addr} (passed by systemd)");
+                } else {
+                    server = server.listen(listener).with_context(|| format!("Failed to listen on inherited tcp socket {addr}"))?;
+                    info!("Listening on {addr} (passed by systemd)");
+                }
+            },
+            InheritedListener::Unix(listener) => {
+                let addr = listener.local_addr().context("Failed to get the address of an inherited unix socket")?;
+                server = server.listen_uds(listener).context("Failed to listen on an inherited unix socket")?;
+                info!("Listening on {} (passed by systemd)", addr.as_pathname().unwrap_or("an unnamed unix socket".as_ref()).display());
+            },
+        }
+    }
+    match metrics_server {
+        None => server.run().await.context("Error while running the server"),
+        Some(metrics_server) => {
+            try_join(server.run(), metrics_server).await.context("Error while running the server")?;
+            Ok(())
+        },
+    }
+}
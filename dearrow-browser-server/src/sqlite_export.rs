@@ -0,0 +1,243 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard, PoisonError}, thread, time::Instant};
+
+use dearrow_parser::{DearrowDB, Extension, ThumbnailFlags, TitleFlags};
+use error_handling::{ErrorContext, ResContext};
+use log::{info, warn};
+use rusqlite::{params, Connection};
+
+use crate::state::DatabaseState;
+
+/// Exports write to the same temporary file, so only one may run at a time
+static EXPORT_LOCK: Mutex<()> = Mutex::new(());
+
+/// An export requested after a reload, with the ID of the reload job
+struct QueuedExport {
+    job_id: u64,
+    state: Arc<DatabaseState>,
+    path: PathBuf,
+}
+
+struct ExportQueue {
+    running: bool,
+    /// Only the newest snapshot waits for the running export, so that older ones can be dropped
+    pending: Option<QueuedExport>,
+}
+
+static EXPORT_QUEUE: Mutex<ExportQueue> = Mutex::new(ExportQueue { running: false, pending: None });
+
+// the queue is only modified in single assignments, a panic while holding the lock can't leave it in an invalid state
+fn export_queue() -> MutexGuard<'static, ExportQueue> {
+    EXPORT_QUEUE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Exports the snapshot in a background thread, or queues it if an export is already running,
+/// replacing the snapshot queued before it
+pub fn export_in_background(job_id: u64, state: Arc<DatabaseState>, path: PathBuf) {
+    let request = QueuedExport { job_id, state, path };
+    {
+        let mut queue = export_queue();
+        if queue.running {
+            if let Some(skipped) = queue.pending.replace(request) {
+                info!("Skipping the SQLite export after reload #{}, as a newer one is queued", skipped.job_id);
+            }
+            return;
+        }
+        queue.running = true;
+    }
+    // reloads run outside of the runtime, so this is a thread instead of a blocking task
+    let spawned = thread::Builder::new()
+        .name("sqlite export".to_owned())
+        .spawn(move || {
+            let mut next = Some(request);
+            while let Some(queued) = next {
+                let start = Instant::now();
+                match export(&queued.state.db, &queued.path) {
+                    Ok(()) => info!("SQLite export after reload #{} finished in {:.2}s", queued.job_id, start.elapsed().as_secs_f64()),
+                    Err(e) => warn!("SQLite export after reload #{} failed: {e:?}", queued.job_id),
+                }
+                drop(queued);
+                let mut queue = export_queue();
+                next = queue.pending.take();
+                queue.running = next.is_some();
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start the SQLite export after reload #{job_id}: {e}");
+        export_queue().running = false;
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE titles (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    video_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    time_submitted INTEGER NOT NULL,
+    votes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    original INTEGER NOT NULL,
+    locked INTEGER NOT NULL,
+    shadow_hidden INTEGER NOT NULL,
+    unverified INTEGER NOT NULL,
+    removed INTEGER NOT NULL,
+    missing_votes INTEGER NOT NULL
+) WITHOUT ROWID;
+CREATE TABLE thumbnails (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    video_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    time_submitted INTEGER NOT NULL,
+    timestamp REAL,
+    votes INTEGER NOT NULL,
+    downvotes INTEGER NOT NULL,
+    original INTEGER NOT NULL,
+    locked INTEGER NOT NULL,
+    shadow_hidden INTEGER NOT NULL,
+    removed INTEGER NOT NULL,
+    missing_votes INTEGER NOT NULL,
+    missing_timestamp INTEGER NOT NULL
+) WITHOUT ROWID;
+CREATE TABLE usernames (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    username TEXT NOT NULL,
+    locked INTEGER NOT NULL
+) WITHOUT ROWID;
+CREATE TABLE vip_users (
+    user_id TEXT PRIMARY KEY NOT NULL
+) WITHOUT ROWID;
+CREATE TABLE video_infos (
+    video_id TEXT PRIMARY KEY NOT NULL,
+    video_duration REAL NOT NULL,
+    has_outro INTEGER NOT NULL
+) WITHOUT ROWID;
+CREATE TABLE uncut_segments (
+    video_id TEXT NOT NULL,
+    offset REAL NOT NULL,
+    length REAL NOT NULL
+);
+CREATE TABLE warnings (
+    warned_user_id TEXT NOT NULL,
+    issuer_user_id TEXT NOT NULL,
+    time_issued INTEGER NOT NULL,
+    extension TEXT NOT NULL,
+    message TEXT NOT NULL,
+    active INTEGER NOT NULL
+);
+";
+
+// created after inserting the rows, which is faster than updating them on every insert
+const INDEXES: &str = "
+CREATE INDEX titles_video_id ON titles (video_id);
+CREATE INDEX titles_user_id ON titles (user_id);
+CREATE INDEX titles_time_submitted ON titles (time_submitted);
+CREATE INDEX thumbnails_video_id ON thumbnails (video_id);
+CREATE INDEX thumbnails_user_id ON thumbnails (user_id);
+CREATE INDEX thumbnails_time_submitted ON thumbnails (time_submitted);
+CREATE INDEX usernames_username ON usernames (username);
+CREATE INDEX uncut_segments_video_id ON uncut_segments (video_id);
+CREATE INDEX warnings_warned_user_id ON warnings (warned_user_id);
+CREATE INDEX warnings_issuer_user_id ON warnings (issuer_user_id);
+";
+
+/// Writes the database to a new sqlite file at `path`, replacing it once the export is complete
+pub fn export(db: &DearrowDB, path: &Path) -> Result<(), ErrorContext> {
+    let _guard = EXPORT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut temp_path = PathBuf::from(path);
+    temp_path.as_mut_os_string().push(".tmp");
+    if temp_path.exists() {
+        fs::remove_file(&temp_path).context("Failed to remove the leftover temporary export file")?;
+    }
+    write_database(db, &temp_path)?;
+    fs::rename(&temp_path, path).with_context(|| format!("Failed to move the export to {}", path.display()))
+}
+
+fn write_database(db: &DearrowDB, path: &Path) -> Result<(), ErrorContext> {
+    let mut conn = Connection::open(path).with_context(|| format!("Failed to create the SQLite database {}", path.display()))?;
+    // the file only gets its final name once complete, so there's nothing to protect from crashes
+    conn.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;").context("Failed to configure the SQLite database")?;
+    conn.execute_batch(SCHEMA).context("Failed to create the tables")?;
+
+    let tx = conn.transaction().context("Failed to start a transaction")?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO titles VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)").context("Failed to prepare the titles statement")?;
+        for t in &db.titles {
+            stmt.execute(params![
+                &*t.uuid, &*t.video_id, &*t.title, &*t.user_id, t.time_submitted, t.votes, t.downvotes,
+                t.flags.contains(TitleFlags::Original),
+                t.flags.contains(TitleFlags::Locked),
+                t.flags.contains(TitleFlags::ShadowHidden),
+                t.flags.contains(TitleFlags::Unverified),
+                t.flags.contains(TitleFlags::Removed),
+                t.flags.contains(TitleFlags::MissingVotes),
+            ]).with_context(|| format!("Failed to insert title {}", t.uuid))?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO thumbnails VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)").context("Failed to prepare the thumbnails statement")?;
+        for t in &db.thumbnails {
+            stmt.execute(params![
+                &*t.uuid, &*t.video_id, &*t.user_id, t.time_submitted, t.timestamp, t.votes, t.downvotes,
+                t.flags.contains(ThumbnailFlags::Original),
+                t.flags.contains(ThumbnailFlags::Locked),
+                t.flags.contains(ThumbnailFlags::ShadowHidden),
+                t.flags.contains(ThumbnailFlags::Removed),
+                t.flags.contains(ThumbnailFlags::MissingVotes),
+                t.flags.contains(ThumbnailFlags::MissingTimestamp),
+            ]).with_context(|| format!("Failed to insert thumbnail {}", t.uuid))?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO usernames VALUES (?1, ?2, ?3)").context("Failed to prepare the usernames statement")?;
+        for u in db.usernames.values() {
+            stmt.execute(params![&*u.user_id, &*u.username, u.locked]).with_context(|| format!("Failed to insert the username of {}", u.user_id))?;
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO vip_users VALUES (?1)").context("Failed to prepare the VIPs statement")?;
+        for user_id in &db.vip_users {
+            stmt.execute(params![&**user_id]).with_context(|| format!("Failed to insert VIP {user_id}"))?;
+        }
+    }
+    {
+        let mut info_stmt = tx.prepare("INSERT INTO video_infos VALUES (?1, ?2, ?3)").context("Failed to prepare the video infos statement")?;
+        let mut segment_stmt = tx.prepare("INSERT INTO uncut_segments VALUES (?1, ?2, ?3)").context("Failed to prepare the uncut segments statement")?;
+        for info in db.video_infos.iter().flat_map(|chunk| chunk.iter()) {
+            info_stmt.execute(params![&*info.video_id, info.video_duration, info.has_outro]).with_context(|| format!("Failed to insert the video info of {}", info.video_id))?;
+            for segment in &info.uncut_segments {
+                segment_stmt.execute(params![&*info.video_id, segment.offset, segment.length]).with_context(|| format!("Failed to insert an uncut segment of {}", info.video_id))?;
+            }
+        }
+    }
+    {
+        let mut stmt = tx.prepare("INSERT INTO warnings VALUES (?1, ?2, ?3, ?4, ?5, ?6)").context("Failed to prepare the warnings statement")?;
+        for w in &db.warnings {
+            let extension = match w.extension {
+                Extension::SponsorBlock => "SponsorBlock",
+                Extension::DeArrow => "DeArrow",
+            };
+            stmt.execute(params![&*w.warned_user_id, &*w.issuer_user_id, w.time_issued, extension, &*w.message, w.active])
+                .with_context(|| format!("Failed to insert a warning for {}", w.warned_user_id))?;
+        }
+    }
+    tx.execute_batch(INDEXES).context("Failed to create the indexes")?;
+    tx.commit().context("Failed to commit the export")?;
+    Ok(())
+}
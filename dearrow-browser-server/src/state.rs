@@ -15,22 +15,69 @@
 *  You should have received a copy of the GNU Affero General Public License
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
-use actix_web::{http::header::EntityTag, rt::{spawn, time::sleep}, web};
+use actix_web::{rt::{spawn, time::sleep}, web};
+use arc_swap::{ArcSwap, ArcSwapOption};
 use chrono::{DateTime, Utc};
-use dearrow_browser_api::sync as api;
-use dearrow_parser::{DearrowDB, StringSet};
+use dearrow_browser_api::sync::{self as api, SubmissionFlag};
+use dearrow_parser::{DearrowDB, StringSet, ThumbnailFlags};
+use enumflags2::BitFlags;
 use error_handling::{bail, ErrContext, ErrorContext, ResContext};
 use futures::{channel::oneshot, future::{BoxFuture, Shared}, join, lock::Mutex, select_biased, FutureExt};
-use log::warn;
-use reqwest::Client;
+use log::{info, warn};
+use reqwest::{Client, ClientBuilder};
 use tokio::fs::read_dir;
-use std::{collections::{HashMap, HashSet}, ffi::OsString, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering::Relaxed}, Arc, RwLock}, time::Instant};
+use std::{collections::{hash_map::Entry, BTreeSet, HashMap, HashSet}, env, ffi::OsString, fs::{self, File}, io::{self, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering::Relaxed}, Arc, OnceLock}, time::{Duration, Instant}};
 use serde::{Serialize, Deserialize};
 
-use crate::{constants::*, innertube, utils::random_b64};
+use crate::{constants::*, innertube, metrics::METRICS, ratelimit::{ClientIp, RateLimiters}, systemd, utils::{self, random_b64}};
 
-pub type DBLock = web::Data<RwLock<DatabaseState>>;
-pub type StringSetLock = web::Data<RwLock<StringSet>>;
+pub type DBState = web::Data<DatabaseHandle>;
+
+/// Holds the live database snapshot, replaced as a whole on every reload.
+///
+/// The snapshot is missing while a low-memory reload is running.
+pub struct DatabaseHandle {
+    current: ArcSwapOption<DatabaseState>,
+    /// Empty cache that the channel caches of new snapshots are created from
+    channel_cache: ChannelCache,
+}
+
+impl DatabaseHandle {
+    /// Creates a handle without a loaded database
+    pub fn new(channel_cache: ChannelCache) -> DatabaseHandle {
+        DatabaseHandle {
+            current: ArcSwapOption::empty(),
+            channel_cache,
+        }
+    }
+
+    pub fn new_channel_cache(&self, string_set: Arc<StringSet>) -> ChannelCache {
+        self.channel_cache.reset(string_set)
+    }
+
+    /// Returns the current snapshot, or a 503 error if the database is not loaded
+    pub fn load(&self) -> utils::Result<Arc<DatabaseState>> {
+        self.try_load().ok_or(utils::Error::Unavailable(DB_UNAVAILABLE_RETRY_AFTER))
+    }
+
+    pub fn try_load(&self) -> Option<Arc<DatabaseState>> {
+        self.current.load_full()
+    }
+
+    pub fn store(&self, state: Arc<DatabaseState>) {
+        self.current.store(Some(state));
+    }
+
+    /// Removes the current snapshot, making the database unavailable until the next [`DatabaseHandle::store`]
+    pub fn take(&self) -> Option<Arc<DatabaseState>> {
+        self.current.swap(None)
+    }
+
+    /// Atomically replaces the current snapshot with a modified copy, if the database is loaded
+    pub fn update(&self, f: impl Fn(&DatabaseState) -> DatabaseState) {
+        self.current.rcu(|state| state.as_deref().map(|state| Arc::new(f(state))));
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
@@ -38,6 +85,8 @@ pub struct AppConfig {
     pub mirror_path: PathBuf,
     pub static_content_path: PathBuf,
     pub listen: ListenConfig,
+    /// Address the server is reachable at from the outside, used for absolute links in feeds and link previews
+    pub public_url: String,
     pub auth_secret: String,
     pub enable_sbserver_emulation: bool,
     pub reqwest_timeout_secs: f64,
@@ -45,7 +94,18 @@ pub struct AppConfig {
     pub startup_timestamp: DateTime<Utc>,
     pub innertube: InnertubeConfig,
     pub enable_timings_header: bool,
+    /// Drop the old database before loading the new one, instead of keeping it available during reloads
+    pub low_memory_reload: bool,
+    /// Write the database to an sqlite file in `cache_path` after every reload
+    pub sqlite_export: bool,
     pub cache_path: PathBuf,
+    pub metrics: MetricsConfig,
+    pub rate_limits: RateLimitConfig,
+    pub tokens: Vec<AdminToken>,
+    pub webhooks: Vec<WebhookConfig>,
+    pub auto_reload: AutoReloadConfig,
+    /// How long to wait for in-flight requests to finish after receiving SIGTERM
+    pub shutdown_timeout_secs: u64,
 }
 
 impl Default for AppConfig {
@@ -54,17 +114,245 @@ impl Default for AppConfig {
             mirror_path: PathBuf::from("./mirror"),
             static_content_path: PathBuf::from("./static"),
             listen: ListenConfig::default(),
+            public_url: "http://localhost:9292".to_owned(),
             auth_secret: random_b64::<64>(),
             enable_sbserver_emulation: false,
             reqwest_timeout_secs: 20.,
             startup_timestamp: Utc::now(),
             innertube: InnertubeConfig::default(),
             enable_timings_header: false,
-            cache_path: PathBuf::from("./cache")
+            low_memory_reload: false,
+            sqlite_export: false,
+            cache_path: PathBuf::from("./cache"),
+            metrics: MetricsConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            tokens: Vec::new(),
+            webhooks: Vec::new(),
+            auto_reload: AutoReloadConfig::default(),
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+pub type ConfigState = web::Data<ConfigHandle>;
+
+/// Holds the current config, along with the HTTP client built from it.
+///
+/// Both are replaced when the config is reloaded, but settings that can't change at runtime keep their values from startup.
+pub struct ConfigHandle {
+    /// The file the config is re-read from, if any
+    path: Option<PathBuf>,
+    current: ArcSwap<AppConfig>,
+    client: ArcSwap<Client>,
+}
+
+impl ConfigHandle {
+    pub fn new(path: Option<PathBuf>, config: AppConfig) -> Result<ConfigHandle, ErrorContext> {
+        Ok(ConfigHandle {
+            path,
+            client: ArcSwap::from_pointee(config.build_client()?),
+            current: ArcSwap::from_pointee(config),
+        })
+    }
+
+    pub fn load(&self) -> Arc<AppConfig> {
+        self.current.load_full()
+    }
+
+    pub fn client(&self) -> Client {
+        Client::clone(&self.client.load())
+    }
+
+    /// Re-reads the config file and applies the settings listed in [`RUNTIME_SETTINGS`].
+    ///
+    /// Returns the changed settings that were not applied.
+    pub fn reload(&self) -> Result<Vec<String>, ErrorContext> {
+        let Some(ref path) = self.path else {
+            bail!("The config was not loaded from a file, so it can't be reloaded");
+        };
+        let new = AppConfig::load(path, false)?;
+        let old = self.load();
+        let toml::Value::Table(old_table) = toml::Value::try_from(&*old).context("Failed to serialize the current config")? else {
+            bail!("The current config was not serialized as a table");
+        };
+        let toml::Value::Table(mut new_table) = toml::Value::try_from(&new).context("Failed to serialize the new config")? else {
+            bail!("The new config was not serialized as a table");
+        };
+        let mut ignored = Vec::new();
+        keep_static_settings(&old_table, &mut new_table, "", &mut ignored);
+        let mut merged: AppConfig = toml::Value::Table(new_table).try_into().context("Failed to deserialize the merged config")?;
+        merged.startup_timestamp = old.startup_timestamp;
+
+        let client = merged.build_client()?;
+        self.current.store(Arc::new(merged));
+        self.client.store(Arc::new(client));
+        for setting in &ignored {
+            warn!("The {setting} setting can't be changed while the server is running, restart the server to apply the new value");
+        }
+        info!("Config reloaded from {}", path.display());
+        Ok(ignored)
+    }
+}
+
+/// Replaces the values of settings not listed in [`RUNTIME_SETTINGS`] with the old ones, collecting the names of changed settings into `ignored`
+fn keep_static_settings(old: &toml::Table, new: &mut toml::Table, prefix: &str, ignored: &mut Vec<String>) {
+    let keys: BTreeSet<String> = old.keys().chain(new.keys()).cloned().collect();
+    for key in keys {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+        if RUNTIME_SETTINGS.contains(&path.as_str()) {
+            continue;
+        }
+        let nested_prefix = format!("{path}.");
+        if RUNTIME_SETTINGS.iter().any(|setting| setting.starts_with(&nested_prefix)) {
+            if let (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) = (old.get(&key), new.get_mut(&key)) {
+                keep_static_settings(old, new, &path, ignored);
+                continue;
+            }
+        }
+        if old.get(&key) != new.get(&key) {
+            ignored.push(path);
+            match old.get(&key) {
+                Some(value) => new.insert(key, value.clone()),
+                None => new.remove(&key),
+            };
         }
     }
 }
 
+impl AppConfig {
+    fn build_client(&self) -> Result<Client, ErrorContext> {
+        ClientBuilder::new()
+            .timeout(Duration::from_secs_f64(self.reqwest_timeout_secs))
+            .build().context("Failed to create a reqwest Client")
+    }
+
+    /// Reads and validates the config file, applying the environment variable overrides.
+    ///
+    /// If `create_if_missing` is set, a missing config file is created with the default config.
+    pub fn load(path: &Path, create_if_missing: bool) -> Result<AppConfig, ErrorContext> {
+        let cfg = AppConfig::read(path, create_if_missing)?;
+        let problems = cfg.problems();
+        if !problems.is_empty() {
+            bail!("Invalid configuration - {}", problems.join(", "));
+        }
+        if cfg.low_memory_reload && !cfg.webhooks.is_empty() {
+            warn!("Webhooks are never sent with low_memory_reload enabled, as the old database isn't kept around to find the changes");
+        }
+        if cfg.low_memory_reload && cfg.sqlite_export {
+            warn!("The SQLite export is skipped with low_memory_reload enabled, as it would keep the old database around during the next reload");
+        }
+        Ok(cfg)
+    }
+
+    /// Reads the config file and applies the environment variable overrides, without validating the settings
+    pub fn read(path: &Path, create_if_missing: bool) -> Result<AppConfig, ErrorContext> {
+        let mut cfg: AppConfig = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).with_context(|| format!("Failed to deserialize contents of {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && create_if_missing => {
+                let cfg = AppConfig::default();
+                let serialized = toml::to_string(&cfg).context("Failed to serialize default AppConfig as TOML")?;
+                let mut file = File::options().write(true).create_new(true).open(path).with_context(|| format!("Failed to create {}", path.display()))?;
+                write!(file, "{serialized}").with_context(|| format!("Failed to write serialized default AppConfig to {}", path.display()))?;
+                cfg
+            },
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        cfg.apply_env_overrides()?;
+        Ok(cfg)
+    }
+
+    /// `public_url` without a trailing slash, for appending paths to
+    pub fn public_origin(&self) -> &str {
+        self.public_url.trim_end_matches('/')
+    }
+
+    /// Lists the settings the server can't run with
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        // sockets passed by systemd are used alongside the configured ones
+        if self.listen.tcp.is_none() && self.listen.unix.is_none() && !systemd::socket_activated() {
+            problems.push("no tcp port or unix socket path specified".to_owned());
+        }
+        if self.listen.tls.is_some() && self.listen.tcp.is_none() && !systemd::socket_activated() {
+            problems.push("tls requires a tcp port to be specified".to_owned());
+        }
+        match reqwest::Url::parse(&self.public_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {},
+            Ok(..) => problems.push("public_url must be an http or https url".to_owned()),
+            Err(e) => problems.push(format!("public_url is not a valid url: {e}")),
+        }
+        if !self.reqwest_timeout_secs.is_finite() || self.reqwest_timeout_secs <= 0. {
+            problems.push("reqwest_timeout_secs must be a positive number".to_owned());
+        }
+        let limits = [
+            ("api", &self.rate_limits.api),
+            ("sbserver", &self.rate_limits.sbserver),
+            ("innertube", &self.rate_limits.innertube),
+            ("channel_fetches", &self.rate_limits.channel_fetches),
+        ];
+        for (name, limit) in limits {
+            let Some(limit) = limit else { continue };
+            if limit.requests == 0 {
+                problems.push(format!("rate_limits.{name}.requests must be at least 1"));
+            }
+            if !limit.period_secs.is_finite() || limit.period_secs <= 0. {
+                problems.push(format!("rate_limits.{name}.period_secs must be a positive number"));
+            }
+        }
+        let auto_reload = &self.auto_reload;
+        for (name, secs) in [("debounce_secs", auto_reload.debounce_secs), ("min_interval_secs", auto_reload.min_interval_secs)] {
+            if Duration::try_from_secs_f64(secs).is_err() {
+                problems.push(format!("auto_reload.{name} must be a non-negative number"));
+            }
+        }
+        if Duration::try_from_secs_f64(auto_reload.poll_interval_secs).is_err() || auto_reload.poll_interval_secs <= 0. {
+            problems.push("auto_reload.poll_interval_secs must be a positive number".to_owned());
+        }
+        // empty secrets would match empty credentials
+        if self.auth_secret.is_empty() {
+            problems.push(format!("auth_secret is empty, set it in the config or the {ENV_AUTH_SECRET} environment variable"));
+        }
+        for token in self.tokens.iter().filter(|t| t.token.is_empty()) {
+            problems.push(format!("token '{}' is empty, set it in the config or the {} environment variable", token.name, token.env_var()));
+        }
+        for webhook in self.webhooks.iter().filter(|w| w.secret.is_empty()) {
+            problems.push(format!("the secret of webhook '{}' is empty, set it in the config or the {} environment variable", webhook.name, webhook.env_var()));
+        }
+        problems
+    }
+
+    /// Replaces secrets with the values of their environment variables, if set
+    fn apply_env_overrides(&mut self) -> Result<(), ErrorContext> {
+        fn var(name: &str) -> Result<Option<String>, ErrorContext> {
+            match env::var(name) {
+                Ok(value) => Ok(Some(value)),
+                Err(env::VarError::NotPresent) => Ok(None),
+                Err(e) => Err(e).with_context(|| format!("Failed to read the {name} environment variable")),
+            }
+        }
+        if let Some(secret) = var(ENV_AUTH_SECRET)? {
+            self.auth_secret = secret;
+        }
+        if let Some(visitor_data) = var(ENV_VISITOR_DATA)? {
+            self.innertube.visitor_data = Some(visitor_data);
+        }
+        if let Some(po_token) = var(ENV_PO_TOKEN)? {
+            self.innertube.po_token = Some(po_token);
+        }
+        for token in &mut self.tokens {
+            if let Some(value) = var(&token.env_var())? {
+                token.token = value;
+            }
+        }
+        for webhook in &mut self.webhooks {
+            if let Some(value) = var(&webhook.env_var())? {
+                webhook.secret = value;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct InnertubeConfig {
@@ -83,11 +371,167 @@ impl Default for InnertubeConfig {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enable: bool,
+    /// If set, `/metrics` is served without authentication on this address only,
+    /// instead of being served on the main listener behind the auth secret
+    pub listen: Option<(String, u16)>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            listen: None,
+        }
+    }
+}
+
+/// Reloading the database automatically when files in `mirror_path` change
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoReloadConfig {
+    pub enable: bool,
+    /// Files are considered fully written once they haven't changed for this long
+    pub debounce_secs: f64,
+    /// Minimum time between the start of two automatic reloads
+    pub min_interval_secs: f64,
+    /// How often the directory is checked for changes when inotify is unavailable
+    pub poll_interval_secs: f64,
+}
+
+impl Default for AutoReloadConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            debounce_secs: 30.,
+            min_interval_secs: 600.,
+            poll_interval_secs: 60.,
+        }
+    }
+}
+
+/// A named token, sent as a bearer token to perform admin actions within its scopes
+#[derive(Serialize, Deserialize)]
+pub struct AdminToken {
+    pub name: String,
+    pub token: String,
+    pub scopes: Vec<TokenScope>,
+}
+
+impl AdminToken {
+    /// Name of the environment variable overriding this token
+    pub fn env_var(&self) -> String {
+        env_var_name(ENV_TOKEN_PREFIX, &self.name)
+    }
+}
+
+/// Uppercases the name and replaces characters other than letters and digits with underscores
+fn env_var_name(prefix: &str, name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    format!("{prefix}{name}")
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    NewTitle,
+    NewThumbnail,
+    TitleUpdated,
+    ThumbnailUpdated,
+}
+
+/// A URL notified of the changes found by reloads that match its rules
+///
+/// Empty rules match everything, and a change has to match all non-empty rules.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of the request body
+    pub secret: String,
+    #[serde(default)]
+    pub events: Vec<WebhookEventKind>,
+    #[serde(default)]
+    pub video_ids: Vec<String>,
+    #[serde(default)]
+    pub user_ids: Vec<String>,
+    /// Handles or UCIDs of channels, resolved through innertube
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Flags the submission must have after the change
+    #[serde(default)]
+    pub flags: Vec<SubmissionFlag>,
+    /// Flags the submission must have had before the change, never matches new submissions
+    #[serde(default)]
+    pub previous_flags: Vec<SubmissionFlag>,
+    /// Flags that must have been set by the change, never matches new submissions
+    #[serde(default)]
+    pub added_flags: Vec<SubmissionFlag>,
+    /// Only match submissions by users with active warnings
+    #[serde(default)]
+    pub warned_users: bool,
+    /// How many times a failed request is retried, with exponential backoff
+    #[serde(default = "WebhookConfig::default_max_retries")]
+    pub max_retries: u32,
+}
+
+impl WebhookConfig {
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    /// Name of the environment variable overriding the secret of this webhook
+    pub fn env_var(&self) -> String {
+        env_var_name(ENV_WEBHOOK_PREFIX, &self.name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    Reload,
+    CacheAdmin,
+    Metrics,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Header containing the client's address, set by a trusted reverse proxy, like `X-Forwarded-For`.
+    /// If unset, or missing from a request, the address of the connecting peer is used.
+    pub trusted_proxy_header: Option<String>,
+    pub api: Option<RateLimit>,
+    pub sbserver: Option<RateLimit>,
+    pub innertube: Option<RateLimit>,
+    /// Limits how often a single client may start fetching a channel that's not cached yet
+    pub channel_fetches: Option<RateLimit>,
+}
+
+/// Allows `requests` requests per `period_secs` seconds, with bursts of up to `requests` requests
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period_secs: f64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ListenConfig {
     pub tcp: Option<(String, u16)>,
     pub unix: Option<String>,
     pub unix_mode: Option<u32>,
+    /// Serves https on the tcp port and inherited tcp sockets instead of plain http
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, starting with the server's certificate
+    pub cert_path: PathBuf,
+    /// PEM file with the private key of the certificate
+    pub key_path: PathBuf,
 }
 
 impl Default for ListenConfig {
@@ -96,28 +540,46 @@ impl Default for ListenConfig {
             tcp: Some(("0.0.0.0".to_owned(), 9292)),
             unix: None,
             unix_mode: None,
+            tls: None,
         }
     }
 }
 
+/// An immutable snapshot of the database, along with the strings it was loaded with
+#[derive(Clone)]
 pub struct DatabaseState {
-    pub db: DearrowDB,
-    pub errors: Box<[ErrorContext]>,
+    pub db: Arc<DearrowDB>,
+    pub string_set: Arc<StringSet>,
+    pub errors: Arc<[ErrorContext]>,
     pub last_updated: i64,
     pub last_modified: i64,
-    pub updating_now: bool,
-    pub etag: Option<EntityTag>,
     pub channel_cache: ChannelCache,
     pub video_info_count: usize,
     pub uncut_segment_count: usize,
+    /// Number of thumbnails with each flag
+    pub thumbnail_flag_counts: Vec<(ThumbnailFlags, usize)>,
+    /// Computed on the first request for the leaderboards
+    pub leaderboards: Arc<OnceLock<api::Leaderboards>>,
 }
 
 impl DatabaseState {
-    pub fn get_etag(&self) -> EntityTag {
-        match &self.etag {
-            None => self.generate_etag(),
-            Some(ref t) => t.clone(),
-        }
+    pub fn new(db: DearrowDB, string_set: Arc<StringSet>, errors: Vec<ErrorContext>, last_modified: i64, channel_cache: ChannelCache) -> DatabaseState {
+        let mut state = DatabaseState {
+            db: Arc::new(db),
+            string_set,
+            errors: errors.into(),
+            last_updated: Utc::now().timestamp_millis(),
+            last_modified,
+            channel_cache,
+            uncut_segment_count: 0,
+            video_info_count: 0,
+            thumbnail_flag_counts: Vec::new(),
+            leaderboards: Arc::default(),
+        };
+        state.uncut_segment_count = state.calculate_uncut_segment_count();
+        state.video_info_count = state.calculate_video_info_count();
+        state.thumbnail_flag_counts = state.calculate_thumbnail_flag_counts();
+        state
     }
 
     pub fn calculate_video_info_count(&self) -> usize {
@@ -128,18 +590,14 @@ impl DatabaseState {
         self.db.video_infos.iter().map(|chunk| chunk.iter().map(|v| v.uncut_segments.len()).sum::<usize>()).sum()
     }
 
-    pub fn generate_etag(&self) -> EntityTag {
-        EntityTag::new_weak(format!(
-            "{}:{}:{}+{}+{}+{}+{}+{}",
-            self.last_updated, 
-            self.last_modified, 
-            self.db.titles.len(), 
-            self.db.thumbnails.len(), 
-            self.db.usernames.len(), 
-            self.db.vip_users.len(),
-            self.video_info_count,
-            self.uncut_segment_count,
-        ))
+    pub fn calculate_thumbnail_flag_counts(&self) -> Vec<(ThumbnailFlags, usize)> {
+        let mut counts: Vec<(ThumbnailFlags, usize)> = BitFlags::<ThumbnailFlags>::all().iter().map(|flag| (flag, 0)).collect();
+        for thumb in &self.db.thumbnails {
+            for (flag, count) in &mut counts {
+                *count += usize::from(thumb.flags.contains(*flag));
+            }
+        }
+        counts
     }
 }
 
@@ -153,9 +611,9 @@ pub struct ChannelCache {
     /// NOTE: Keys of this hashmap are NOT stored in the `StringSet`!
     data_cache: Arc<Mutex<HashMap<Arc<str>, ChannelDataCacheEntry>>>,
     fscache_count_cache: Arc<Mutex<Option<FSCacheCountCache>>>,
-    string_set: Arc<RwLock<StringSet>>,
-    config: Arc<AppConfig>,
-    client: reqwest::Client,
+    string_set: Arc<StringSet>,
+    config: Arc<ConfigHandle>,
+    rate_limiters: Arc<RateLimiters>,
 }
 
 #[derive(Debug)]
@@ -267,28 +725,32 @@ pub struct ChannelData {
 pub enum GetChannelOutput {
     Pending(ChannelFetchProgress),
     Resolved(Arc<ChannelData>),
+    /// The requester may not start a new fetch until the given duration passes
+    RateLimited(Duration),
 }
 
 impl ChannelCache {
-    pub fn new(string_set: Arc<RwLock<StringSet>>, config: Arc<AppConfig>, client: Client) -> ChannelCache {
+    pub fn new(string_set: Arc<StringSet>, config: Arc<ConfigHandle>, rate_limiters: Arc<RateLimiters>) -> ChannelCache {
         ChannelCache { 
             handle_to_ucid_cache: Arc::default(),
             data_cache: Arc::default(), 
             fscache_count_cache: Arc::default(),
             string_set,
             config,
-            client,
+            rate_limiters,
         }
     }
 
-    pub fn reset(&self) -> ChannelCache {
+    /// Creates an empty cache sharing the configuration, for use with the given `StringSet`
+    #[must_use]
+    pub fn reset(&self, string_set: Arc<StringSet>) -> ChannelCache {
         ChannelCache { 
             handle_to_ucid_cache: Arc::default(),
             data_cache: Arc::default(), 
             fscache_count_cache: self.fscache_count_cache.clone(),
-            string_set: self.string_set.clone(),
+            string_set,
             config: self.config.clone(),
-            client: self.client.clone(),
+            rate_limiters: self.rate_limiters.clone(),
         }
     }
 
@@ -296,6 +758,10 @@ impl ChannelCache {
         self.data_cache.lock().await.len()
     }
 
+    pub async fn num_channels_pending(&self) -> usize {
+        self.data_cache.lock().await.values().filter(|e| matches!(e, ChannelDataCacheEntry::Pending { .. })).count()
+    }
+
     pub async fn num_channels_fscached(&self) -> usize {
         let mut cache = self.fscache_count_cache.lock().await;
         let should_replace = match *cache {
@@ -303,7 +769,7 @@ impl ChannelCache {
             Some(ref cache) => cache.timestamp.elapsed() > FSCACHE_SIZE_CACHE_DURATION,
         };
         if should_replace {
-            *cache = Some(FSCacheCountCache::new(self.config.clone()));
+            *cache = Some(FSCacheCountCache::new(self.config.load()));
         }
         let fut = cache.as_ref().unwrap().future.clone();
         drop(cache);
@@ -315,11 +781,12 @@ impl ChannelCache {
     }
 
     async fn fetch_channel(&self, ucid: &Arc<str>, progress: ChannelFetchProgress) -> Result<Arc<ChannelData>, ErrorContext> {
-        let videos_task = spawn(innertube::browse_channel(self.client.clone(), self.config.clone(), &IT_BROWSE_VIDEOS, ucid.clone(), progress.videos.clone()));
-        let vods_task   = spawn(innertube::browse_channel(self.client.clone(), self.config.clone(), &IT_BROWSE_LIVE,   ucid.clone(), progress.vods.clone()));
-        let shorts_task = spawn(innertube::browse_channel(self.client.clone(), self.config.clone(), &IT_BROWSE_SHORTS, ucid.clone(), progress.shorts.clone()));
-        let releases_tab_task  = spawn(innertube::browse_releases_tab(self.client.clone(), self.config.clone(), ucid.clone(), progress.releases_tab.clone()));
-        let releases_home_task = spawn(innertube::browse_releases_homepage(self.client.clone(), self.config.clone(), ucid.clone(), progress.releases_home.clone()));
+        let (client, config) = (self.config.client(), self.config.load());
+        let videos_task = spawn(innertube::browse_channel(client.clone(), config.clone(), &IT_BROWSE_VIDEOS, ucid.clone(), progress.videos.clone()));
+        let vods_task   = spawn(innertube::browse_channel(client.clone(), config.clone(), &IT_BROWSE_LIVE,   ucid.clone(), progress.vods.clone()));
+        let shorts_task = spawn(innertube::browse_channel(client.clone(), config.clone(), &IT_BROWSE_SHORTS, ucid.clone(), progress.shorts.clone()));
+        let releases_tab_task  = spawn(innertube::browse_releases_tab(client.clone(), config.clone(), ucid.clone(), progress.releases_tab.clone()));
+        let releases_home_task = spawn(innertube::browse_releases_homepage(client.clone(), config.clone(), ucid.clone(), progress.releases_home.clone()));
 
         let videos = videos_task.await.context("Video fetching task panicked")?.context("Failed to fetch all videos")?;
         let vods   = vods_task.await.context("VOD fetching task panicked")?.context("Failed to fetch all VODs")?;
@@ -327,7 +794,7 @@ impl ChannelCache {
         let releases_tab = releases_tab_task.await.context("Releases (via tab) fetching task panicked")?.context("Failed to fetch all releases from the tab")?;
         let releases_home = releases_home_task.await.context("Releases (via homepage) fetching task panicked")?.context("Failed to fetch all releases from the homepage")?;
 
-        let string_set = self.string_set.read().map_err(|_| SS_READ_ERR.clone())?;
+        let string_set = &self.string_set;
         let num_releases = releases_tab.iter().map(Vec::len).sum::<usize>() + releases_home.iter().map(Vec::len).sum::<usize>();
         Ok(Arc::new(ChannelData {
             channel_name: videos.name.into(),
@@ -355,7 +822,8 @@ impl ChannelCache {
         };
     }
 
-    pub async fn get_channel(&self, handle: &str) -> Result<GetChannelOutput, ErrorContext> {
+    /// Starting a new fetch counts towards the `requester`'s channel fetch rate limit
+    pub async fn get_channel(&self, handle: &str, requester: ClientIp) -> Result<GetChannelOutput, ErrorContext> {
         let ucid = if UCID_REGEX.is_match(handle) {
             handle.into()
         } else {
@@ -372,7 +840,7 @@ impl ChannelCache {
 
             let ucid_future = {
                 let mut ucid_cache = self.handle_to_ucid_cache.lock().await;
-                ucid_cache.entry(handle).or_insert_with_key(|handle| Self::handle_to_ucid(self.client.clone(), handle.clone()).boxed().shared()).clone()
+                ucid_cache.entry(handle).or_insert_with_key(|handle| Self::handle_to_ucid(self.config.client(), handle.clone()).boxed().shared()).clone()
             };
 
             ucid_future.await.context("Failed to convert handle to UCID")?
@@ -380,15 +848,27 @@ impl ChannelCache {
 
         let channel_data_entry = {
             let mut channel_data_cache = self.data_cache.lock().await;
-            channel_data_cache.entry(ucid).or_insert_with_key(|ucid| {
-                let progress: ChannelFetchProgress = ChannelFetchProgress::default();
-                let (sender, receiver) = oneshot::channel();
-                spawn(Self::fetch_channel_task(self.clone(), ucid.clone(), progress.clone(), sender));
-                ChannelDataCacheEntry::Pending { 
-                    future: receiver.shared(),
-                    progress,
-                }
-            }).clone()
+            match channel_data_cache.entry(ucid) {
+                Entry::Occupied(entry) => {
+                    METRICS.channel_cache_hits.fetch_add(1, Relaxed);
+                    entry.get().clone()
+                },
+                Entry::Vacant(entry) => {
+                    if let ClientIp(Some(ip)) = requester {
+                        if let Err(retry_after) = self.rate_limiters.channel_fetches.check(ip) {
+                            return Ok(GetChannelOutput::RateLimited(retry_after));
+                        }
+                    }
+                    METRICS.channel_cache_misses.fetch_add(1, Relaxed);
+                    let progress: ChannelFetchProgress = ChannelFetchProgress::default();
+                    let (sender, receiver) = oneshot::channel();
+                    spawn(Self::fetch_channel_task(self.clone(), entry.key().clone(), progress.clone(), sender));
+                    entry.insert(ChannelDataCacheEntry::Pending { 
+                        future: receiver.shared(),
+                        progress,
+                    }).clone()
+                },
+            }
         };
 
         Ok::<usize, ErrorContext>(0)?;
@@ -403,3 +883,65 @@ impl ChannelCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::keep_static_settings;
+
+    fn merge(old: &str, new: &str) -> (toml::Table, Vec<String>) {
+        let old: toml::Table = toml::from_str(old).unwrap();
+        let mut new: toml::Table = toml::from_str(new).unwrap();
+        let mut ignored = Vec::new();
+        keep_static_settings(&old, &mut new, "", &mut ignored);
+        (new, ignored)
+    }
+
+    #[test]
+    fn nested_runtime_setting_is_applied() {
+        let (merged, ignored) = merge(
+            "[innertube]\nenable = true\npo_token = \"old\"",
+            "[innertube]\nenable = true\npo_token = \"new\"",
+        );
+        assert_eq!(merged["innertube"]["po_token"].as_str(), Some("new"));
+        assert!(ignored.is_empty());
+    }
+
+    #[test]
+    fn static_setting_is_reverted() {
+        let (merged, ignored) = merge(
+            "auth_secret = \"a\"\n[listen]\ntcp = [\"127.0.0.1\", 9292]",
+            "auth_secret = \"b\"\n[listen]\ntcp = [\"0.0.0.0\", 80]",
+        );
+        assert_eq!(merged["listen"]["tcp"][1].as_integer(), Some(9292));
+        assert_eq!(merged["auth_secret"].as_str(), Some("b"));
+        assert_eq!(ignored, ["listen"]);
+    }
+
+    #[test]
+    fn keys_added_to_nested_table() {
+        let (merged, ignored) = merge(
+            "[innertube]\nenable = true",
+            "[innertube]\nenable = true\nvisitor_data = \"new\"\nfuture_setting = 1",
+        );
+        assert_eq!(merged["innertube"]["visitor_data"].as_str(), Some("new"));
+        assert!(!merged["innertube"].as_table().unwrap().contains_key("future_setting"));
+        assert_eq!(ignored, ["innertube.future_setting"]);
+    }
+
+    #[test]
+    fn keys_removed_from_nested_table() {
+        let (merged, ignored) = merge(
+            "[innertube]\nenable = false\npo_token = \"old\"",
+            "[innertube]\npo_token = \"old\"",
+        );
+        assert_eq!(merged["innertube"]["enable"].as_bool(), Some(false));
+        assert_eq!(ignored, ["innertube.enable"]);
+
+        let (merged, ignored) = merge(
+            "[innertube]\nenable = true\npo_token = \"old\"",
+            "[innertube]\nenable = true",
+        );
+        assert!(!merged["innertube"].as_table().unwrap().contains_key("po_token"));
+        assert!(ignored.is_empty());
+    }
+}
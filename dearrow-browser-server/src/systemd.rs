@@ -0,0 +1,79 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Socket activation, following <https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html>
+use std::{env, io, mem, net::TcpListener, os::{fd::{FromRawFd, RawFd}, unix::net::UnixListener}, process, sync::LazyLock};
+
+use error_handling::{bail, ErrorContext, ResContext};
+
+use crate::constants::*;
+
+/// The first file descriptor passed by the service manager
+const LISTEN_FDS_START: RawFd = 3;
+
+pub enum InheritedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Checked once, as the variables are removed by [`take_listeners`]
+static SOCKET_ACTIVATED: LazyLock<bool> = LazyLock::new(|| env::var(ENV_LISTEN_PID).is_ok_and(|pid| pid.parse() == Ok(process::id())));
+
+/// Whether the service manager passed sockets to this process
+pub fn socket_activated() -> bool {
+    *SOCKET_ACTIVATED
+}
+
+/// Takes ownership of the sockets passed by the service manager
+///
+/// Must only be called once, as the file descriptors are closed when the returned listeners are dropped.
+pub fn take_listeners() -> Result<Vec<InheritedListener>, ErrorContext> {
+    if !socket_activated() {
+        return Ok(Vec::new());
+    }
+    let count: RawFd = env::var(ENV_LISTEN_FDS).context("LISTEN_PID was set without LISTEN_FDS")?
+        .parse().context("Failed to parse LISTEN_FDS")?;
+    // like sd_listen_fds(), so that child processes don't try to take the sockets too
+    env::remove_var(ENV_LISTEN_PID);
+    env::remove_var(ENV_LISTEN_FDS);
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).map(|fd| {
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len: libc::socklen_t = mem::size_of_val(&addr).try_into().expect("sockaddr_storage should fit in socklen_t");
+        // SAFETY: addr is large enough for any socket address, and len holds its size
+        if unsafe { libc::getsockname(fd, (&raw mut addr).cast(), &raw mut len) } != 0 {
+            return Err(io::Error::last_os_error()).with_context(|| format!("Inherited file descriptor {fd} is not a socket"));
+        }
+        let mut socket_type: libc::c_int = 0;
+        let mut len: libc::socklen_t = mem::size_of_val(&socket_type).try_into().expect("c_int should fit in socklen_t");
+        // SAFETY: socket_type is a c_int, as expected for SO_TYPE, and len holds its size
+        if unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_TYPE, (&raw mut socket_type).cast(), &raw mut len) } != 0 {
+            return Err(io::Error::last_os_error()).with_context(|| format!("Failed to get the type of inherited socket {fd}"));
+        }
+        if socket_type != libc::SOCK_STREAM {
+            bail!("Inherited socket {} is not a stream socket (socket type {})", fd, socket_type);
+        }
+        // keep the sockets from leaking into child processes, as they are owned by this process now
+        // SAFETY: fd is a valid file descriptor
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        // SAFETY: the service manager passed ownership of the socket to this process, and nothing else uses it
+        match i32::from(addr.ss_family) {
+            libc::AF_INET | libc::AF_INET6 => Ok(InheritedListener::Tcp(unsafe { TcpListener::from_raw_fd(fd) })),
+            libc::AF_UNIX => Ok(InheritedListener::Unix(unsafe { UnixListener::from_raw_fd(fd) })),
+            family => bail!("Inherited file descriptor {} is not a tcp or unix socket (address family {})", fd, family),
+        }
+    }).collect()
+}
@@ -0,0 +1,74 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use error_handling::{bail, ErrorContext, ResContext};
+use rustls::{crypto::ring, pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer}, server::{ClientHello, ResolvesServerCert}, sign::CertifiedKey, ServerConfig};
+
+use crate::state::TlsConfig;
+
+/// Hands out the current certificate, which can be swapped without restarting the listener
+#[derive(Debug)]
+pub struct CertResolver {
+    config: TlsConfig,
+    key: ArcSwap<CertifiedKey>,
+}
+
+impl CertResolver {
+    pub fn new(config: &TlsConfig) -> Result<CertResolver, ErrorContext> {
+        Ok(CertResolver {
+            key: ArcSwap::from_pointee(load_certified_key(config)?),
+            config: config.clone(),
+        })
+    }
+
+    /// Re-reads the certificate and key files, keeping the old certificate if they are invalid
+    pub fn reload(&self) -> Result<(), ErrorContext> {
+        self.key.store(Arc::new(load_certified_key(&self.config)?));
+        Ok(())
+    }
+
+    pub fn server_config(self: Arc<Self>) -> Result<ServerConfig, ErrorContext> {
+        Ok(ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions().context("Failed to select TLS protocol versions")?
+            .with_no_client_auth()
+            .with_cert_resolver(self))
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.load_full())
+    }
+}
+
+pub fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey, ErrorContext> {
+    let cert_path = config.cert_path.display();
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .with_context(|| format!("Failed to read certificates from {cert_path}"))?;
+    if certs.is_empty() {
+        bail!("{} does not contain any certificates", cert_path);
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key_path).with_context(|| format!("Failed to read the private key from {}", config.key_path.display()))?;
+    let key = ring::sign::any_supported_type(&key).context("Unsupported private key type")?;
+    let certified_key = CertifiedKey::new(certs, key);
+    certified_key.keys_match().with_context(|| format!("The private key does not belong to the certificate in {cert_path}"))?;
+    Ok(certified_key)
+}
@@ -15,9 +15,9 @@
 *  You should have received a copy of the GNU Affero General Public License
 *  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
-use std::{ffi::CString, fmt::{Debug, Display}, fs, mem::MaybeUninit, ops::{Deref, DerefMut}, os::{fd::AsRawFd, unix::ffi::OsStrExt}, path::{Path, PathBuf}, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use std::{ffi::CString, fmt::{Debug, Display}, fs, mem::MaybeUninit, ops::{Deref, DerefMut}, os::{fd::AsRawFd, unix::ffi::OsStrExt}, path::{Path, PathBuf}, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-use actix_web::{dev::Extensions, http::{header::{HeaderMap, TryIntoHeaderPair}, StatusCode}, HttpResponse, Responder, ResponseError};
+use actix_web::{dev::Extensions, http::{header::{HeaderMap, TryIntoHeaderPair, RETRY_AFTER}, StatusCode}, HttpResponse, Responder, ResponseError};
 use base64::prelude::{BASE64_URL_SAFE_NO_PAD, Engine};
 use error_handling::{ErrContext, ErrorContext, IntoErrorIterator, ResContext};
 use serde::de::DeserializeOwned;
@@ -31,6 +31,10 @@ pub enum Error {
     #[allow(clippy::enum_variant_names)]
     ErrorContext(ErrorContext, StatusCode),
     EmptyStatus(StatusCode),
+    /// 429 Too Many Requests, the client may retry after the given duration
+    RateLimited(Duration),
+    /// 503 Service Unavailable while the database is being loaded, the client may retry after the given duration
+    Unavailable(Duration),
 }
 
 impl Debug for Error {
@@ -38,6 +42,8 @@ impl Debug for Error {
         match self {
             Error::ErrorContext(ref err, _) => Debug::fmt(err, f),
             Error::EmptyStatus(status) => f.debug_tuple("Error::EmptyStatus").field(status).finish(),
+            Error::RateLimited(retry_after) => f.debug_tuple("Error::RateLimited").field(retry_after).finish(),
+            Error::Unavailable(retry_after) => f.debug_tuple("Error::Unavailable").field(retry_after).finish(),
         }
     }
 }
@@ -46,6 +52,8 @@ impl Display for Error {
         match self {
             Error::ErrorContext(ref err, _) => Display::fmt(err, f),
             Error::EmptyStatus(status) => write!(f, "{status}"),
+            Error::RateLimited(retry_after) => write!(f, "Rate limit exceeded, retry in {} seconds", retry_after_secs(*retry_after)),
+            Error::Unavailable(retry_after) => write!(f, "The database is being loaded, retry in {} seconds", retry_after_secs(*retry_after)),
         }
     }
 }
@@ -57,8 +65,11 @@ impl From<ErrorContext> for Error {
 impl std::error::Error for Error {}
 impl ResponseError for Error {
     fn status_code(&self) -> StatusCode {
-        let (Error::ErrorContext(_, status) | Error::EmptyStatus(status)) = self;
-        *status
+        match self {
+            Error::ErrorContext(_, status) | Error::EmptyStatus(status) => *status,
+            Error::RateLimited(..) => StatusCode::TOO_MANY_REQUESTS,
+            Error::Unavailable(..) => StatusCode::SERVICE_UNAVAILABLE,
+        }
     }
 
     fn error_response(&self) -> HttpResponse {
@@ -72,15 +83,24 @@ impl ResponseError for Error {
                 builder.json(err.serializable_copy())
             },
             Error::EmptyStatus(..) => builder.finish(),
+            Error::RateLimited(retry_after) | Error::Unavailable(retry_after) => {
+                builder.insert_header((RETRY_AFTER, retry_after_secs(*retry_after)));
+                builder.body(self.to_string())
+            },
         }
     }
 }
 
+/// `Retry-After` only supports whole seconds, round up so that the client doesn't retry too early
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
 impl Error {
     pub fn set_status(self, status: StatusCode) -> Self {
         match self {
             Error::ErrorContext(err, _) => Error::ErrorContext(err, status),
-            Error::EmptyStatus(..) => Error::EmptyStatus(status),
+            Error::EmptyStatus(..) | Error::RateLimited(..) | Error::Unavailable(..) => Error::EmptyStatus(status),
         }
     }
 }
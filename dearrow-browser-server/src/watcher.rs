@@ -0,0 +1,191 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::BTreeMap, ffi::{CString, OsString}, fs::{self, File}, io::{self, Read}, os::{fd::{AsRawFd, FromRawFd, OwnedFd}, unix::ffi::OsStrExt}, path::Path, sync::Arc, thread, time::{Duration, Instant, SystemTime}};
+
+use actix_web::web;
+use error_handling::{ErrorContext, ResContext};
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::feed::SubmissionFeed;
+use crate::reload::ReloadJobs;
+use crate::routes::{begin_reload, do_reload};
+use crate::state::{AppConfig, DBState};
+use crate::webhooks::Webhooks;
+
+/// Modification time and size of every CSV file in the mirror directory
+type Snapshot = BTreeMap<OsString, (SystemTime, u64)>;
+
+fn is_csv(name: &OsString) -> bool {
+    Path::new(name).extension().is_some_and(|ext| ext == "csv")
+}
+
+fn snapshot(dir: &Path) -> Snapshot {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => {
+            warn!("Failed to list files in the mirror directory '{}': {e}", dir.display());
+            return Snapshot::new();
+        },
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| is_csv(&entry.file_name()))
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            Some((entry.file_name(), (meta.modified().ok()?, meta.len())))
+        })
+        .collect()
+}
+
+fn checksum(dir: &Path, files: &Snapshot) -> Result<[u8; 32], ErrorContext> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    for name in files.keys() {
+        hasher.update(name.as_bytes());
+        let mut file = File::open(dir.join(name)).with_context(|| format!("Failed to open {}", name.to_string_lossy()))?;
+        loop {
+            match file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&buffer[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {}", name.to_string_lossy())),
+            }
+        }
+    }
+    Ok(hasher.finalize().into())
+}
+
+enum ChangeSource {
+    Inotify(OwnedFd),
+    Polling,
+}
+
+impl ChangeSource {
+    fn new(dir: &Path) -> ChangeSource {
+        match Self::init_inotify(dir) {
+            Ok(fd) => ChangeSource::Inotify(fd),
+            Err(e) => {
+                warn!("Failed to watch the mirror directory using inotify, falling back to polling: {e}");
+                ChangeSource::Polling
+            },
+        }
+    }
+
+    fn init_inotify(dir: &Path) -> io::Result<OwnedFd> {
+        let path = CString::new(dir.as_os_str().as_bytes()).map_err(io::Error::other)?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MODIFY | libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+
+    /// Blocks until something in the directory may have changed, or `timeout` passes
+    fn wait(&self, timeout: Duration) {
+        match self {
+            ChangeSource::Polling => thread::sleep(timeout),
+            ChangeSource::Inotify(fd) => {
+                let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+                let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+                unsafe { libc::poll(&raw mut pollfd, 1, timeout) };
+                // the events themselves don't matter, snapshots are compared instead
+                let mut buffer = [0u8; 4096];
+                while unsafe { libc::read(fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) } > 0 {}
+            },
+        }
+    }
+}
+
+/// Watches the mirror directory in a background thread, reloading the database after the CSV files change
+pub fn spawn(db_state: DBState, config: Arc<AppConfig>, feed: web::Data<SubmissionFeed>, webhooks: web::Data<Webhooks>, jobs: web::Data<ReloadJobs>) {
+    thread::Builder::new()
+        .name("mirror watcher".to_owned())
+        .spawn(move || watch(&db_state, &config, &feed, &webhooks, &jobs))
+        .expect("Should be able to spawn the mirror watcher thread");
+}
+
+fn watch(db_state: &DBState, config: &AppConfig, feed: &SubmissionFeed, webhooks: &Webhooks, jobs: &ReloadJobs) {
+    let dir = config.mirror_path.as_path();
+    let settings = &config.auto_reload;
+    let source = ChangeSource::new(dir);
+    let mut last_snapshot = snapshot(dir);
+    let mut last_checksum = checksum(dir, &last_snapshot).inspect_err(|e| warn!("Failed to checksum the mirror: {e:?}")).ok();
+    let mut last_reload = Instant::now();
+    info!("Watching {} for changes", dir.display());
+
+    loop {
+        source.wait(Duration::from_secs_f64(settings.poll_interval_secs));
+        let mut current = snapshot(dir);
+        if current == last_snapshot {
+            continue;
+        }
+
+        let min_interval = Duration::from_secs_f64(settings.min_interval_secs);
+        loop {
+            // wait for the mirror to finish writing all files
+            loop {
+                thread::sleep(Duration::from_secs_f64(settings.debounce_secs));
+                let settled = snapshot(dir);
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+            let Some(remaining) = min_interval.checked_sub(last_reload.elapsed()) else { break };
+            // the mirror may start writing again while we wait, so it has to settle again afterwards
+            info!("Mirror changed, waiting {:.1}s before reloading", remaining.as_secs_f64());
+            thread::sleep(remaining);
+            current = snapshot(dir);
+        }
+
+        let new_checksum = match checksum(dir, &current) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                warn!("Failed to checksum the mirror: {e:?}");
+                None
+            },
+        };
+        if new_checksum.is_some() && new_checksum == last_checksum {
+            info!("Mirror files were touched, but their contents did not change - skipping the reload");
+            last_snapshot = current;
+            continue;
+        }
+
+        let job = match begin_reload(jobs, "the mirror watcher".to_owned()) {
+            Ok(job) => job,
+            Err(e) => {
+                // the change stays pending, as the snapshot still differs from the last one
+                warn!("Could not start a reload after the mirror changed, retrying later: {e}");
+                continue;
+            },
+        };
+        last_reload = Instant::now();
+        // failed reloads, such as of half-written files, are retried once min_interval_secs passes,
+        // as the snapshot still differs from the last one
+        if do_reload(&job, db_state, config, feed, webhooks).is_ok() {
+            last_snapshot = current;
+            last_checksum = new_checksum;
+        }
+    }
+}
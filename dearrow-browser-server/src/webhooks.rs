@@ -0,0 +1,304 @@
+/* This file is part of the DeArrow Browser project - https://github.com/mini-bomba/DeArrowBrowser
+*
+*  Copyright (C) 2024 mini_bomba
+*
+*  This program is free software: you can redistribute it and/or modify
+*  it under the terms of the GNU Affero General Public License as published by
+*  the Free Software Foundation, either version 3 of the License, or
+*  (at your option) any later version.
+*
+*  This program is distributed in the hope that it will be useful,
+*  but WITHOUT ANY WARRANTY; without even the implied warranty of
+*  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*  GNU Affero General Public License for more details.
+*
+*  You should have received a copy of the GNU Affero General Public License
+*  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::{collections::{HashMap, HashSet}, fmt::Write, sync::Arc};
+
+use actix_web::rt::{self, time::sleep};
+use dearrow_browser_api::sync::{IntoWithDatabase, SubmissionFlag, WebhookEvent, WebhookPayload};
+use dearrow_parser::{DearrowDB, Thumbnail, ThumbnailFlags, Title, TitleFlags};
+use log::{info, warn};
+use reqwest::{header::CONTENT_TYPE, Client, StatusCode};
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+use crate::constants::*;
+use crate::ratelimit::ClientIp;
+use crate::state::{ConfigState, DatabaseState, GetChannelOutput, WebhookConfig, WebhookEventKind};
+
+/// A change found by comparing two databases, referring to a submission of the new one by its index
+enum Change {
+    NewTitle(usize),
+    NewThumbnail(usize),
+    TitleUpdated { index: usize, previous: Vec<SubmissionFlag> },
+    ThumbnailUpdated { index: usize, previous: Vec<SubmissionFlag> },
+}
+
+struct Batch {
+    state: Arc<DatabaseState>,
+    changes: Vec<Change>,
+}
+
+/// Reports the changes found by reloads to the webhooks from the config
+pub struct Webhooks {
+    config: ConfigState,
+    sender: mpsc::UnboundedSender<Batch>,
+}
+
+impl Webhooks {
+    /// Starts the delivery task, must be called from within the actix runtime
+    pub fn new(config: ConfigState) -> Webhooks {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        rt::spawn(process_batches(config.clone(), receiver));
+        Webhooks { config, sender }
+    }
+
+    /// Finds the changes between the two databases, then matches and delivers them in the background
+    pub fn report_reload(&self, old_db: &DearrowDB, new_state: Arc<DatabaseState>) {
+        if self.config.load().webhooks.is_empty() {
+            return;
+        }
+        let changes = find_changes(old_db, &new_state.db);
+        if changes.is_empty() {
+            return;
+        }
+        info!("Matching {} changed submissions against webhooks", changes.len());
+        // only fails if the delivery task is gone, which happens when the runtime shuts down
+        let _ = self.sender.send(Batch { state: new_state, changes });
+    }
+}
+
+fn title_flags(title: &Title) -> Vec<SubmissionFlag> {
+    [
+        (TitleFlags::Original, SubmissionFlag::Original),
+        (TitleFlags::Locked, SubmissionFlag::Locked),
+        (TitleFlags::ShadowHidden, SubmissionFlag::ShadowHidden),
+        (TitleFlags::Unverified, SubmissionFlag::Unverified),
+        (TitleFlags::Removed, SubmissionFlag::Removed),
+    ].into_iter().filter(|(flag, _)| title.flags.contains(*flag)).map(|(_, flag)| flag).collect()
+}
+
+fn thumbnail_flags(thumb: &Thumbnail) -> Vec<SubmissionFlag> {
+    [
+        (ThumbnailFlags::Original, SubmissionFlag::Original),
+        (ThumbnailFlags::Locked, SubmissionFlag::Locked),
+        (ThumbnailFlags::ShadowHidden, SubmissionFlag::ShadowHidden),
+        (ThumbnailFlags::Removed, SubmissionFlag::Removed),
+    ].into_iter().filter(|(flag, _)| thumb.flags.contains(*flag)).map(|(_, flag)| flag).collect()
+}
+
+/// Finds new submissions, and submissions whose flags changed
+fn find_changes(old_db: &DearrowDB, new_db: &DearrowDB) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let old_titles: HashMap<&str, &Title> = old_db.titles.iter().map(|t| (&*t.uuid, t)).collect();
+    for (index, title) in new_db.titles.iter().enumerate() {
+        match old_titles.get(&*title.uuid) {
+            None => changes.push(Change::NewTitle(index)),
+            Some(old) if old.flags != title.flags => {
+                let previous = title_flags(old);
+                // changes of the flags of missing vote rows aren't reported
+                if previous != title_flags(title) {
+                    changes.push(Change::TitleUpdated { index, previous });
+                }
+            },
+            Some(_) => {},
+        }
+    }
+    drop(old_titles);
+    let old_thumbs: HashMap<&str, &Thumbnail> = old_db.thumbnails.iter().map(|t| (&*t.uuid, t)).collect();
+    for (index, thumb) in new_db.thumbnails.iter().enumerate() {
+        match old_thumbs.get(&*thumb.uuid) {
+            None => changes.push(Change::NewThumbnail(index)),
+            Some(old) if old.flags != thumb.flags => {
+                let previous = thumbnail_flags(old);
+                if previous != thumbnail_flags(thumb) {
+                    changes.push(Change::ThumbnailUpdated { index, previous });
+                }
+            },
+            Some(_) => {},
+        }
+    }
+    changes
+}
+
+/// Resolves the videos of the channels of a webhook, waiting for channels that are still being fetched
+async fn channel_videos(webhook: &WebhookConfig, state: &DatabaseState) -> HashSet<Arc<str>> {
+    let mut videos = HashSet::new();
+    for channel in &webhook.channels {
+        let mut polls = 0;
+        loop {
+            // webhooks aren't requested by a client, don't count them towards a rate limit
+            match state.channel_cache.get_channel(channel, ClientIp(None)).await {
+                Ok(GetChannelOutput::Resolved(data)) => {
+                    videos.extend(data.video_ids.iter().cloned());
+                    break;
+                },
+                Ok(GetChannelOutput::Pending(..) | GetChannelOutput::RateLimited(..)) if polls < WEBHOOK_CHANNEL_MAX_POLLS => {
+                    polls += 1;
+                    sleep(WEBHOOK_CHANNEL_POLL_INTERVAL).await;
+                },
+                Ok(GetChannelOutput::Pending(..) | GetChannelOutput::RateLimited(..)) => {
+                    warn!("Channel {channel} of webhook '{}' is taking too long to fetch, skipping it", webhook.name);
+                    break;
+                },
+                Err(e) => {
+                    warn!("Failed to fetch channel {channel} of webhook '{}': {e:?}", webhook.name);
+                    break;
+                },
+            }
+        }
+    }
+    videos
+}
+
+/// Builds the events for all changes matching the rules of the webhook other than `channels`, along with their video IDs
+fn matching_events(webhook: &WebhookConfig, batch: &Batch, warned_users: &HashSet<&str>) -> Vec<(Arc<str>, WebhookEvent)> {
+    let db = &batch.state.db;
+    let matches = |kind: WebhookEventKind, video_id: &Arc<str>, user_id: &str, flags: &[SubmissionFlag], previous: Option<&[SubmissionFlag]>| {
+        (webhook.events.is_empty() || webhook.events.contains(&kind))
+        && (webhook.video_ids.is_empty() || webhook.video_ids.iter().any(|v| **v == **video_id))
+        && (webhook.user_ids.is_empty() || webhook.user_ids.iter().any(|u| u == user_id))
+        && webhook.flags.iter().all(|f| flags.contains(f))
+        && (webhook.previous_flags.is_empty() || previous.is_some_and(|p| webhook.previous_flags.iter().all(|f| p.contains(f))))
+        && (webhook.added_flags.is_empty() || previous.is_some_and(|p| webhook.added_flags.iter().all(|f| flags.contains(f) && !p.contains(f))))
+        && (!webhook.warned_users || warned_users.contains(user_id))
+    };
+    let diff = |previous: &[SubmissionFlag], flags: &[SubmissionFlag]| -> (Vec<SubmissionFlag>, Vec<SubmissionFlag>) {
+        (
+            flags.iter().filter(|f| !previous.contains(f)).copied().collect(),
+            previous.iter().filter(|f| !flags.contains(f)).copied().collect(),
+        )
+    };
+    batch.changes.iter().filter_map(|change| match *change {
+        Change::NewTitle(index) => {
+            let title = &db.titles[index];
+            matches(WebhookEventKind::NewTitle, &title.video_id, &title.user_id, &title_flags(title), None)
+                .then(|| (title.video_id.clone(), WebhookEvent::NewTitle { title: title.into_with_db(db) }))
+        },
+        Change::NewThumbnail(index) => {
+            let thumb = &db.thumbnails[index];
+            matches(WebhookEventKind::NewThumbnail, &thumb.video_id, &thumb.user_id, &thumbnail_flags(thumb), None)
+                .then(|| (thumb.video_id.clone(), WebhookEvent::NewThumbnail { thumbnail: thumb.into_with_db(db) }))
+        },
+        Change::TitleUpdated { index, ref previous } => {
+            let title = &db.titles[index];
+            let flags = title_flags(title);
+            matches(WebhookEventKind::TitleUpdated, &title.video_id, &title.user_id, &flags, Some(previous)).then(|| {
+                let (added_flags, removed_flags) = diff(previous, &flags);
+                (title.video_id.clone(), WebhookEvent::TitleUpdated { title: title.into_with_db(db), added_flags, removed_flags })
+            })
+        },
+        Change::ThumbnailUpdated { index, ref previous } => {
+            let thumb = &db.thumbnails[index];
+            let flags = thumbnail_flags(thumb);
+            matches(WebhookEventKind::ThumbnailUpdated, &thumb.video_id, &thumb.user_id, &flags, Some(previous)).then(|| {
+                let (added_flags, removed_flags) = diff(previous, &flags);
+                (thumb.video_id.clone(), WebhookEvent::ThumbnailUpdated { thumbnail: thumb.into_with_db(db), added_flags, removed_flags })
+            })
+        },
+    }).collect()
+}
+
+fn send(config: &ConfigState, webhook: &WebhookConfig, state: &DatabaseState, events: Vec<WebhookEvent>) {
+    let payload = WebhookPayload {
+        webhook: webhook.name.as_str().into(),
+        last_updated: state.last_updated,
+        events,
+    };
+    let body = serde_json::to_vec(&payload).expect("Webhook payloads should be serializable");
+    rt::spawn(deliver(config.client(), webhook.clone(), body));
+}
+
+/// Drops the events for videos outside the channels of the webhook, then sends the rest
+async fn filter_by_channels(config: ConfigState, webhook: WebhookConfig, state: Arc<DatabaseState>, events: Vec<(Arc<str>, WebhookEvent)>) {
+    let videos = channel_videos(&webhook, &state).await;
+    let events: Vec<WebhookEvent> = events.into_iter().filter(|(video_id, _)| videos.contains(video_id)).map(|(_, event)| event).collect();
+    if !events.is_empty() {
+        send(&config, &webhook, &state, events);
+    }
+}
+
+async fn process_batches(config: ConfigState, mut receiver: mpsc::UnboundedReceiver<Batch>) {
+    while let Some(batch) = receiver.recv().await {
+        let current = config.load();
+        let warned_users: HashSet<&str> = batch.state.db.warnings.iter().filter(|w| w.active).map(|w| &*w.warned_user_id).collect();
+        for webhook in &current.webhooks {
+            if !webhook.channels.is_empty() && !current.innertube.enable {
+                warn!("Webhook '{}' filters by channel, but innertube is disabled, skipping it", webhook.name);
+                continue;
+            }
+            let events = matching_events(webhook, &batch, &warned_users);
+            if events.is_empty() {
+                continue;
+            }
+            if webhook.channels.is_empty() {
+                send(&config, webhook, &batch.state, events.into_iter().map(|(_, event)| event).collect());
+            } else {
+                // fetching the channels can take minutes, don't hold up other webhooks and batches
+                rt::spawn(filter_by_channels(config.clone(), webhook.clone(), batch.state.clone(), events));
+            }
+        }
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the body, in the format of the signature header
+fn signature(key: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC should accept keys of any length");
+    mac.update(body);
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        write!(signature, "{byte:02x}").expect("Writing to a String should not fail");
+    }
+    signature
+}
+
+/// Sends the payload, retrying on network errors, server errors and rate limits
+async fn deliver(client: Client, webhook: WebhookConfig, body: Vec<u8>) {
+    let signature = signature(webhook.secret.as_bytes(), &body);
+    let mut delay = WEBHOOK_RETRY_DELAY;
+    for attempt in 0..=webhook.max_retries {
+        if attempt > 0 {
+            sleep(delay).await;
+            delay *= 2;
+        }
+        let result = client.post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send().await;
+        match result {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) if resp.status().is_server_error() || resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                warn!("Webhook '{}' responded with {} (attempt {} of {})", webhook.name, resp.status(), attempt + 1, webhook.max_retries + 1);
+            },
+            Ok(resp) => {
+                warn!("Webhook '{}' rejected the request with {}, not retrying", webhook.name, resp.status());
+                return;
+            },
+            Err(e) => warn!("Failed to send a request to webhook '{}' (attempt {} of {}): {e}", webhook.name, attempt + 1, webhook.max_retries + 1),
+        }
+    }
+    warn!("Giving up on delivering changes to webhook '{}'", webhook.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::signature;
+
+    // test cases 2 and 6 of RFC 4231
+    #[test]
+    fn signature_matches_rfc_4231() {
+        assert_eq!(
+            signature(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        );
+        assert_eq!(
+            signature(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First"),
+            "sha256=60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+        );
+    }
+}
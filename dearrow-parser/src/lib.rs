@@ -238,8 +238,59 @@ pub struct DBPaths {
     pub warnings: PathBuf,
 }
 
+impl DBPaths {
+    /// Paths of the files in a mirror directory
+    pub fn in_dir(dir: &Path) -> DBPaths {
+        DBPaths {
+            thumbnails: dir.join("thumbnails.csv"),
+            thumbnail_timestamps: dir.join("thumbnailTimestamps.csv"),
+            thumbnail_votes: dir.join("thumbnailVotes.csv"),
+            titles: dir.join("titles.csv"),
+            title_votes: dir.join("titleVotes.csv"),
+            usernames: dir.join("userNames.csv"),
+            vip_users: dir.join("vipUsers.csv"),
+            sponsor_times: dir.join("sponsorTimes.csv"),
+            warnings: dir.join("warnings.csv"),
+        }
+    }
+
+    pub fn all(&self) -> [&Path; 9] {
+        [
+            &self.thumbnails,
+            &self.thumbnail_timestamps,
+            &self.thumbnail_votes,
+            &self.titles,
+            &self.title_votes,
+            &self.usernames,
+            &self.vip_users,
+            &self.sponsor_times,
+            &self.warnings,
+        ]
+    }
+}
+
 pub type LoadResult = (DearrowDB, Vec<ErrorContext>);
 
+/// A phase of loading a [`DearrowDB`], in the order they're executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadPhase {
+    Thumbnails,
+    Titles,
+    Usernames,
+    VipUsers,
+    SponsorTimes,
+    Warnings,
+}
+
+/// Receives progress updates while a [`DearrowDB`] is being loaded
+pub trait LoadProgress {
+    fn phase_started(&self, _phase: LoadPhase) {}
+    /// Called for every CSV row read during the current phase, including rows of auxiliary files (like votes)
+    fn row_read(&self) {}
+}
+
+impl LoadProgress for () {}
+
 impl DearrowDB {
     pub fn sort(&mut self) {
         self.titles.sort_unstable_by(|a, b| a.time_submitted.cmp(&b.time_submitted));
@@ -251,23 +302,18 @@ impl DearrowDB {
     }
 
     pub fn load_dir(dir: &Path, string_set: &mut StringSet) -> Result<LoadResult> {
-        DearrowDB::load(
-            &DBPaths {
-                thumbnails: dir.join("thumbnails.csv"),
-                thumbnail_timestamps: dir.join("thumbnailTimestamps.csv"),
-                thumbnail_votes: dir.join("thumbnailVotes.csv"),
-                titles: dir.join("titles.csv"),
-                title_votes: dir.join("titleVotes.csv"),
-                usernames: dir.join("userNames.csv"),
-                vip_users: dir.join("vipUsers.csv"),
-                sponsor_times: dir.join("sponsorTimes.csv"),
-                warnings: dir.join("warnings.csv"),
-            },
-            string_set,
-        )
+        DearrowDB::load_dir_with_progress(dir, string_set, &())
+    }
+
+    pub fn load_dir_with_progress(dir: &Path, string_set: &mut StringSet, progress: &dyn LoadProgress) -> Result<LoadResult> {
+        DearrowDB::load_with_progress(&DBPaths::in_dir(dir), string_set, progress)
     }
 
     pub fn load(paths: &DBPaths, string_set: &mut StringSet) -> Result<LoadResult> {
+        DearrowDB::load_with_progress(paths, string_set, &())
+    }
+
+    pub fn load_with_progress(paths: &DBPaths, string_set: &mut StringSet, progress: &dyn LoadProgress) -> Result<LoadResult> {
         // Briefly open each file in read-only to check if they exist before continuing to parse
         File::open(&paths.thumbnails).context("Could not open the thumbnails file")?;
         File::open(&paths.thumbnail_timestamps).context("Could not open the thumbnail timestamps file")?;
@@ -283,33 +329,40 @@ impl DearrowDB {
         let mut errors: Vec<ErrorContext> = Vec::new();
         
         info!("Loading thumbnails...");
-        let thumbnails = Self::load_thumbnails(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::Thumbnails);
+        let thumbnails = Self::load_thumbnails(paths, string_set, &mut errors, progress)?;
 
         info!("Loading titles...");
-        let titles = Self::load_titles(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::Titles);
+        let titles = Self::load_titles(paths, string_set, &mut errors, progress)?;
 
         info!("Loading usernames...");
-        let usernames = Self::load_usernames(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::Usernames);
+        let usernames = Self::load_usernames(paths, string_set, &mut errors, progress)?;
 
         info!("Loading VIPs...");
-        let vip_users = Self::load_vips(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::VipUsers);
+        let vip_users = Self::load_vips(paths, string_set, &mut errors, progress)?;
 
         info!("Extracting video info from SponsorBlock segments...");
-        let video_infos = Self::load_video_info(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::SponsorTimes);
+        let video_infos = Self::load_video_info(paths, string_set, &mut errors, progress)?;
 
         info!("Loading warnings...");
-        let warnings = Self::load_warnings(paths, string_set, &mut errors)?;
+        progress.phase_started(LoadPhase::Warnings);
+        let warnings = Self::load_warnings(paths, string_set, &mut errors, progress)?;
 
         info!("DearrowDB loaded!");
         Ok((DearrowDB {titles, thumbnails, usernames, vip_users, video_infos, warnings}, errors))
     }
 
-    fn load_thumbnails(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<Vec<Thumbnail>> {
+    fn load_thumbnails(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<Vec<Thumbnail>> {
         // Load the entirety of thumbnailTimestamps and thumbnailVotes into HashMaps, while
         // deduplicating strings
         let thumbnail_timestamps: HashMap<Arc<str>, csv_data::ThumbnailTimestamps> = csv::Reader::from_path(&paths.thumbnail_timestamps)
             .context("Could not initialize csv reader for thumbnail timestamps")?
             .into_deserialize::<csv_data::ThumbnailTimestamps>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing thumbnail timestamps") {
                 Ok(mut thumb) => {
                     thumb.dedupe(string_set);
@@ -325,6 +378,7 @@ impl DearrowDB {
         let thumbnail_votes: HashMap<Arc<str>, csv_data::ThumbnailVotes> = csv::Reader::from_path(&paths.thumbnail_votes)
             .context("Could not initialize csv reader for thumbnail votes")?
             .into_deserialize::<csv_data::ThumbnailVotes>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing thumbnail votes") {
                 Ok(mut thumb) => {
                     thumb.dedupe(string_set);
@@ -342,6 +396,7 @@ impl DearrowDB {
         Ok(csv::Reader::from_path(&paths.thumbnails)
             .context("Could not initialize csv reader for thumbnails")?
             .into_deserialize::<csv_data::Thumbnail>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing thumbnails") {
                 Ok(mut thumb) => {
                     thumb.dedupe(string_set);
@@ -366,10 +421,11 @@ impl DearrowDB {
             .collect())
     }
 
-    fn load_titles(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<Vec<Title>> {
+    fn load_titles(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<Vec<Title>> {
         let title_votes: HashMap<Arc<str>, csv_data::TitleVotes> = csv::Reader::from_path(&paths.title_votes)
             .context("Could not initialize csv reader for title votes")?
             .into_deserialize::<csv_data::TitleVotes>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing title votes") {
                 Ok(mut title) => {
                     title.dedupe(string_set);
@@ -385,6 +441,7 @@ impl DearrowDB {
         Ok(csv::Reader::from_path(&paths.titles)
             .context("Could not initialize csv reader for titles")?
             .into_deserialize::<csv_data::Title>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing titles") {
                 Ok(mut title) => {
                     title.dedupe(string_set);
@@ -408,10 +465,11 @@ impl DearrowDB {
             .collect())
     }
 
-    fn load_usernames(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<HashMap<Arc<str>, Username>> {
+    fn load_usernames(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<HashMap<Arc<str>, Username>> {
         Ok(csv::Reader::from_path(&paths.usernames)
             .context("could not initialize csv reader for usernames")?
             .into_deserialize::<csv_data::Username>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing usernames") {
                 Ok(mut username) => {
                     username.dedupe(string_set);
@@ -426,10 +484,11 @@ impl DearrowDB {
             .collect())
     }
 
-    fn load_vips(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<HashSet<Arc<str>>> {
+    fn load_vips(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<HashSet<Arc<str>>> {
         Ok(csv::Reader::from_path(&paths.vip_users)
             .context("could not initialize csv reader for VIP users")?
             .into_deserialize::<csv_data::VIPUser>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context("Error while deserializing vip users") {
                 Ok(mut vip) => {
                     vip.dedupe(string_set);
@@ -444,32 +503,35 @@ impl DearrowDB {
     }
 
     #[allow(clippy::float_cmp)]
-    fn load_video_info(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<Box<[Box<[VideoInfo]>]>> {
+    fn load_video_info(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<Box<[Box<[VideoInfo]>]>> {
         const HASHBLOCK_RANGE: std::ops::RangeInclusive<usize> = 0..=u16::MAX as usize;
         let mut segments: Box<[HashMap<Arc<str>, Vec<csv_data::TrimmedSponsorTime>>]> = HASHBLOCK_RANGE.map(|_| HashMap::new()).collect();
         let mut video_durations: Box<[HashMap<Arc<str>, csv_data::VideoDuration>]> = HASHBLOCK_RANGE.map(|_| HashMap::new()).collect();
         csv::Reader::from_path(&paths.sponsor_times)
             .context("could not initialize csv reader for SponsorBlock segments")?
             .into_deserialize::<csv_data::SponsorTime>()
-            .for_each(|result| match result.context("Error while deserializing SponsorBlock segments") {
-                Ok(mut segment) => {
-                    segment.dedupe(string_set);
-                    if let Some((hash_prefix, duration, segment)) = segment.filter_and_split() {
-                        video_durations[hash_prefix as usize].entry(duration.video_id.clone())
-                            .and_modify(|d| {
-                                if duration.video_duration != 0. && (d.time_submitted > duration.time_submitted || d.video_duration == 0.) {
-                                    let mut duration = duration.clone();
-                                    duration.has_outro |= d.has_outro;
-                                    *d = duration;
-                                } else {
-                                    d.has_outro |= duration.has_outro;
-                                }
-                            })
-                            .or_insert(duration);
-                        segments[hash_prefix as usize].entry(segment.video_id.clone()).or_default().push(segment);
-                    }
-                },
-                Err(error) => errors.push(error),
+            .for_each(|result| {
+                progress.row_read();
+                match result.context("Error while deserializing SponsorBlock segments") {
+                    Ok(mut segment) => {
+                        segment.dedupe(string_set);
+                        if let Some((hash_prefix, duration, segment)) = segment.filter_and_split() {
+                            video_durations[hash_prefix as usize].entry(duration.video_id.clone())
+                                .and_modify(|d| {
+                                    if duration.video_duration != 0. && (d.time_submitted > duration.time_submitted || d.video_duration == 0.) {
+                                        let mut duration = duration.clone();
+                                        duration.has_outro |= d.has_outro;
+                                        *d = duration;
+                                    } else {
+                                        d.has_outro |= duration.has_outro;
+                                    }
+                                })
+                                .or_insert(duration);
+                            segments[hash_prefix as usize].entry(segment.video_id.clone()).or_default().push(segment);
+                        }
+                    },
+                    Err(error) => errors.push(error),
+                }
             });
         Ok(HASHBLOCK_RANGE.map(|hash_prefix| {
                 video_durations[hash_prefix].values()
@@ -543,11 +605,12 @@ impl DearrowDB {
             .collect())
     }
 
-    fn load_warnings(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>) -> Result<Vec<Warning>> {
+    fn load_warnings(paths: &DBPaths, string_set: &mut StringSet, errors: &mut Vec<ErrorContext>, progress: &dyn LoadProgress) -> Result<Vec<Warning>> {
         const CONTEXT: &str = "Error while deserializing warnings";
         Ok(csv::Reader::from_path(&paths.warnings)
             .context("could not initialize csv reader for warnings")?
             .into_deserialize::<csv_data::Warning>()
+            .inspect(|_| progress.row_read())
             .filter_map(|result| match result.context(CONTEXT).and_then(|w| Warning::try_from(w).context(CONTEXT)) {
                 Ok(mut tip) => {
                     tip.dedupe(string_set);